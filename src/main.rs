@@ -1,6 +1,76 @@
-use std::collections::HashMap;
-use std::io::{self, BufRead};
-use chrono::{NaiveDateTime, Duration};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime};
+
+const DEFAULT_ADMIN_TOKEN: &str = "admin";
+const DEFAULT_MIN_CONNECTION_MINUTES: u32 = 60;
+const FARE_PER_MILE: u32 = 100;
+const MILES_VALIDITY_MONTHS: u32 = 24;
+const MIN_EXIT_ROW_AGE: u32 = 15;
+const MIN_ADULT_AGE: u32 = 15;
+const KIOSK_SESSION_MINUTES: i64 = 10;
+const CHECKIN_MINUTES_PER_PASSENGER: usize = 3;
+const MAX_REVIEW_COMMENT_CHARS: usize = 500;
+const SEAT_LOCK_MINUTES: i64 = 15;
+const MAX_SERVICE_FEE: u32 = 100_000;
+const SEAT_ROWS: u32 = 20;
+const DEFAULT_FIRST_FLYER_DISCOUNT_PCT: u32 = 10;
+const WAITLIST_WINDOW_HOURS: i64 = 2;
+const DEFAULT_WAITLIST_MAX_RETRY: u32 = 10;
+const SWAP_REQUEST_HOURS: i64 = 24;
+const MAX_PASSENGER_NAME_CHARS: usize = 100;
+const DEFAULT_REMINDER_HOURS: u32 = 24;
+const NEXT_AVAILABLE_SEARCH_DAYS: usize = 366;
+const ANALYTICS_CSV_HEADER: &str = concat!(
+    "reservation_id,flight_id,date,seat_class_index,",
+    "price,service_fee,commission,is_cancelled,is_no_show"
+);
+const BASE_CURRENCY: &str = "JPY";
+const IDEMPOTENCY_KEY_HOURS: i64 = 24;
+const KNOWN_COMMANDS: &[&str] = &[
+    "reserve:", "cancel:", "seat-search:", "get-reservations:", "calendar-view:",
+    "admin-reservations:", "flight-search:", "next-available:", "block-user:", "unblock-user:",
+    "list-blocked-users:", "start-auction:", "bid:", "close-auction:", "set-alert-prefs:",
+    "due-reminders:", "top-flights:", "route-statistics:", "checkin:", "auto-cancel:",
+    "get-vouchers:", "flexible-search:", "set-seat-weights:", "adjacent-seats:", "batch:",
+    "clone-schedule:", "create-itinerary:", "auto-create-itinerary:", "set-min-connection-time:",
+    "recode-airport:", "delay-compensation:", "propagate-delay:", "generate-promos:",
+    "update-class-seat-types:", "add-insurance:", "claim-insurance:", "set-seat-preference:",
+    "satisfaction-report:", "export-analytics:", "export-analytics-json:", "passengers-by-class:",
+    "boarding-list:", "signed-manifest:", "verify-manifest:", "add-announcement:",
+    "remove-announcement:", "set-language:", "start-recording:", "stop-recording:", "replay:",
+    "delete-user-data:", "set-exchange-rate:", "wallet-deposit:", "wallet-pay:", "get-debit-log:",
+    "wallet-balance:", "get-miles:", "expire-miles:", "merge-users:", "booking-streak:",
+    "audit-log:", "query-audit-log:", "add-group-rule:", "add-capacity-alert:",
+    "list-capacity-alerts:", "purge-idempotency-keys:", "bulk-reserve:", "price-history:",
+    "price-trend:", "export-seat-map:", "refund-estimate:", "set-commission-rate:",
+    "agent-commission:", "set-utc-offset:", "export-ical:", "update-surcharge:", "mark-exit-rows:",
+    "set-passenger-info:", "bulk-price-update:", "set-peak-dates:", "set-rebook-protection:",
+    "force-rebook:", "set-checkpoint:", "import-reservations:", "import-users:", "get-profile:",
+    "repair-partial-import:", "consistency-check:", "fix-orphaned-locks:", "reservation-debug:",
+    "generate-test-data:", "reset-counter:", "remove-flight:", "get-all-seats:", "set-cache-ttl:",
+    "clear-cache:", "set-output-delimiter:", "adjust-boundary:", "merge-classes:",
+    "forecast-demand:", "class-revenue-breakdown:", "retroactive-fix:", "simulate-price-change:",
+    "empty-flights:", "load-factor-report:", "export-events:", "rebuild-from-events:",
+    "join-waitlist:", "register-task:", "run-due-tasks:", "prune-waitlist:",
+    "set-waitlist-max-retry:", "set-daily-reservation-limit:", "set-rate-limit:", "has-flown:",
+    "split-reservation:", "name-change:", "create-swap:", "respond-swap:", "purge-expired-locks:",
+    "set-lock-expiry-fee:", "lock-seat:", "transfer-lock:", "confirm-lock:", "submit-review:",
+    "get-reviews:", "set-first-flyer-discount:", "set-service-fee:", "set-fee-refundable:",
+    "start-kiosk-session:", "kiosk-reserve:", "kiosk-cancel:", "kiosk-checkin:",
+    "join-checkin-queue:", "serve-checkin-queue:", "queue-wait:", "get-failed-commands:",
+];
+const MAX_SUGGESTION_DISTANCE: usize = 5;
+const DEFAULT_RNG_SEED: u64 = 0x5eed_f11e;
+const TEST_DATA_DAYS: u64 = 30;
+const TEST_DATA_MAX_FLIGHTS: u32 = 1000;
+const TEST_DATA_MAX_RESERVATIONS: u32 = 100_000;
+const DEFAULT_LANGUAGE: &str = "en";
+const REDACTED: &str = "[redacted]";
+const DEFAULT_MESSAGES: &str = include_str!("../lang/en.txt");
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum SeatType {
@@ -35,10 +105,263 @@ impl SeatType {
     }
 }
 
+struct SeatTypeWeight {
+    seat_type: SeatType,
+    weight: f32,
+}
+
+impl SeatTypeWeight {
+    fn defaults() -> Vec<SeatTypeWeight> {
+        [(SeatType::A, 1.0), (SeatType::B, 0.7), (SeatType::C, 0.7), (SeatType::D, 1.0)]
+            .into_iter()
+            .map(|(seat_type, weight)| SeatTypeWeight { seat_type, weight })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    fn parse(input: &str) -> Option<JsonValue> {
+        let chars: Vec<char> = input.chars().collect();
+        let mut pos = 0;
+        let value = Self::parse_value(&chars, &mut pos)?;
+        Self::skip_whitespace(&chars, &mut pos);
+        if pos != chars.len() {
+            return None;
+        }
+        Some(value)
+    }
+
+    fn skip_whitespace(chars: &[char], pos: &mut usize) {
+        while *pos < chars.len() && chars[*pos].is_whitespace() {
+            *pos += 1;
+        }
+    }
+
+    fn parse_value(chars: &[char], pos: &mut usize) -> Option<JsonValue> {
+        Self::skip_whitespace(chars, pos);
+        match chars.get(*pos)? {
+            '{' => {
+                *pos += 1;
+                let mut entries = vec![];
+                Self::skip_whitespace(chars, pos);
+                if chars.get(*pos) == Some(&'}') {
+                    *pos += 1;
+                    return Some(JsonValue::Object(entries));
+                }
+                loop {
+                    Self::skip_whitespace(chars, pos);
+                    let key = match Self::parse_value(chars, pos)? {
+                        JsonValue::String(key) => key,
+                        _ => return None,
+                    };
+                    Self::skip_whitespace(chars, pos);
+                    if chars.get(*pos) != Some(&':') {
+                        return None;
+                    }
+                    *pos += 1;
+                    entries.push((key, Self::parse_value(chars, pos)?));
+                    Self::skip_whitespace(chars, pos);
+                    match chars.get(*pos)? {
+                        ',' => *pos += 1,
+                        '}' => {
+                            *pos += 1;
+                            return Some(JsonValue::Object(entries));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            '[' => {
+                *pos += 1;
+                let mut items = vec![];
+                Self::skip_whitespace(chars, pos);
+                if chars.get(*pos) == Some(&']') {
+                    *pos += 1;
+                    return Some(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(Self::parse_value(chars, pos)?);
+                    Self::skip_whitespace(chars, pos);
+                    match chars.get(*pos)? {
+                        ',' => *pos += 1,
+                        ']' => {
+                            *pos += 1;
+                            return Some(JsonValue::Array(items));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            '"' => {
+                *pos += 1;
+                let mut text = String::new();
+                loop {
+                    let c = *chars.get(*pos)?;
+                    *pos += 1;
+                    match c {
+                        '"' => return Some(JsonValue::String(text)),
+                        '\\' => {
+                            let escaped = *chars.get(*pos)?;
+                            *pos += 1;
+                            text.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                'r' => '\r',
+                                other => other,
+                            });
+                        }
+                        other => text.push(other),
+                    }
+                }
+            }
+            _ => {
+                let start = *pos;
+                while *pos < chars.len()
+                    && !",]}".contains(chars[*pos])
+                    && !chars[*pos].is_whitespace()
+                {
+                    *pos += 1;
+                }
+                let word: String = chars[start..*pos].iter().collect();
+                match word.as_str() {
+                    "null" => Some(JsonValue::Null),
+                    "true" => Some(JsonValue::Bool(true)),
+                    "false" => Some(JsonValue::Bool(false)),
+                    _ => word.parse().ok().map(JsonValue::Number),
+                }
+            }
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn as_u32(&self) -> Option<u32> {
+        match self {
+            JsonValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => {
+                Some(*n as u32)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl JsonValue {
+    fn to_pretty_string(&self, depth: usize) -> String {
+        let indent = |depth: usize| "  ".repeat(depth);
+        match self {
+            JsonValue::Array(items) if !items.is_empty() => {
+                let lines: Vec<String> = items
+                    .iter()
+                    .map(|item| {
+                        format!("{}{}", indent(depth + 1), item.to_pretty_string(depth + 1))
+                    })
+                    .collect();
+                format!("[\n{}\n{}]", lines.join(",\n"), indent(depth))
+            }
+            JsonValue::Object(entries) if !entries.is_empty() => {
+                let lines: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}{}: {}",
+                            indent(depth + 1),
+                            JsonValue::String(key.clone()),
+                            value.to_pretty_string(depth + 1)
+                        )
+                    })
+                    .collect();
+                format!("{{\n{}\n{}}}", lines.join(",\n"), indent(depth))
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::String(text) => {
+                write!(f, "\"")?;
+                for c in text.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\r' => write!(f, "\\r")?,
+                        other => write!(f, "{}", other)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            JsonValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", JsonValue::String(key.clone()), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SeatState {
+    Free,
+    TentativeLock(u64),
+    Reserved,
+}
+
 #[derive(Clone)]
 struct SeatClass {
     column: u32,
     price: u32,
+    allowed_seat_types: Option<Vec<SeatType>>,
 }
 
 #[derive(Clone)]
@@ -49,31 +372,157 @@ struct Flight {
     departure_time: String,
     arrival_time: String,
     seat_classes: Vec<SeatClass>,
+    service_date: Option<String>,
+    announcements: Vec<String>,
+    fuel_surcharge: u32,
+    exit_rows: HashSet<u32>,
+    is_removed: bool,
+    utc_offset_minutes: i32,
 }
 
-impl Flight {
-    fn new(
-        flight_id: u32,
-        departure_airport: u32,
-        arrival_airport: u32,
-        departure_time: String,
-        arrival_time: String,
-        seat_classes: Vec<SeatClass>,
-    ) -> Self {
-        Flight {
+#[derive(Clone, Default)]
+struct SeatClassBuilder {
+    seat_classes: Vec<SeatClass>,
+}
+
+impl SeatClassBuilder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_class(mut self, column: u32, price: u32) -> Result<Self, String> {
+        let previous = self.seat_classes.last().map_or(0, |seat_class| seat_class.column);
+        if column <= previous {
+            return Err(format!(
+                "seat class column {} must be greater than {}",
+                column, previous
+            ));
+        }
+        self.seat_classes.push(SeatClass {
+            column,
+            price,
+            allowed_seat_types: None,
+        });
+        Ok(self)
+    }
+}
+
+impl From<Vec<(u32, u32)>> for SeatClassBuilder {
+    fn from(classes: Vec<(u32, u32)>) -> Self {
+        classes
+            .into_iter()
+            .try_fold(SeatClassBuilder::new(), |builder, (column, price)| {
+                builder.add_class(column, price)
+            })
+            .unwrap()
+    }
+}
+
+struct FlightBuilder {
+    flight_id: u32,
+    departure_airport: u32,
+    arrival_airport: u32,
+    departure_time: String,
+    arrival_time: String,
+    seat_classes: Vec<SeatClass>,
+}
+
+impl FlightBuilder {
+    fn new(flight_id: u32) -> Self {
+        FlightBuilder {
             flight_id,
-            departure_airport,
-            arrival_airport,
-            departure_time,
-            arrival_time,
-            seat_classes,
+            departure_airport: 0,
+            arrival_airport: 0,
+            departure_time: String::new(),
+            arrival_time: String::new(),
+            seat_classes: vec![],
+        }
+    }
+
+    fn route(mut self, departure_airport: u32, arrival_airport: u32) -> Self {
+        self.departure_airport = departure_airport;
+        self.arrival_airport = arrival_airport;
+        self
+    }
+
+    fn schedule(mut self, departure_time: String, arrival_time: String) -> Self {
+        self.departure_time = departure_time;
+        self.arrival_time = arrival_time;
+        self
+    }
+
+    fn with_seat_class(mut self, seat_classes: SeatClassBuilder) -> Self {
+        self.seat_classes = seat_classes.seat_classes;
+        self
+    }
+
+    fn build(self) -> Flight {
+        Flight {
+            flight_id: self.flight_id,
+            departure_airport: self.departure_airport,
+            arrival_airport: self.arrival_airport,
+            departure_time: self.departure_time,
+            arrival_time: self.arrival_time,
+            seat_classes: self.seat_classes,
+            service_date: None,
+            announcements: vec![],
+            fuel_surcharge: 0,
+            exit_rows: HashSet::new(),
+            is_removed: false,
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+impl Flight {
+    fn class_capacity(&self, class_index: usize) -> u32 {
+        let start_row = match class_index {
+            1 => 1,
+            _ => self.seat_classes[class_index - 2].column + 1,
+        };
+        (self.seat_classes[class_index - 1].column + 1 - start_row)
+            * SeatType::variants().len() as u32
+    }
+
+    fn operates_on(&self, date: &str) -> bool {
+        !self.is_removed
+            && self
+                .service_date
+                .as_deref()
+                .is_none_or(|service_date| service_date == date)
+    }
+
+    fn sort_seat_classes(&mut self) {
+        self.seat_classes.sort_by_key(|seat_class| seat_class.column);
+    }
+
+    fn verify_seat_class_order(&self) -> Result<(), String> {
+        let mut previous = 0;
+        for (i, seat_class) in self.seat_classes.iter().enumerate() {
+            if seat_class.column <= previous {
+                return Err(format!(
+                    "flight_id={} class={} column={} previous={}",
+                    self.flight_id,
+                    i + 1,
+                    seat_class.column,
+                    previous
+                ));
+            }
+            previous = seat_class.column;
         }
+        Ok(())
+    }
+
+    fn seat_count(&self) -> u32 {
+        self.seat_classes
+            .last()
+            .map_or(0, |seat_class| seat_class.column * SeatType::variants().len() as u32)
     }
 
     fn get_seat_class(&self, seat_id: &str) -> Option<(u32, u32)> {
-        let (row_part, seat_type_char) = seat_id.split_at(seat_id.len() - 1);
-        let row: u32 = row_part.parse().ok()?;
-        let _seat_type = SeatType::from_char(seat_type_char.chars().next()?)?;
+        let (split, seat_type_char) = seat_id.char_indices().last()?;
+        let row: u32 = seat_id[..split].parse().ok()?;
+        let _seat_type = SeatType::from_char(seat_type_char)?;
 
         for (i, seat_class) in self.seat_classes.iter().enumerate() {
             if row <= seat_class.column {
@@ -82,16 +531,75 @@ impl Flight {
         }
         None
     }
+
+    fn violates_seat_policy(
+        &self,
+        seat_id: &str,
+        passenger_age: Option<u32>,
+        needs_assistance: bool,
+    ) -> bool {
+        let row = seat_id.char_indices().last().map(|(split, _)| seat_id[..split].parse());
+        let row: u32 = match row {
+            Some(Ok(row)) => row,
+            _ => return false,
+        };
+        self.exit_rows.contains(&row)
+            && (needs_assistance || passenger_age.is_some_and(|age| age < MIN_EXIT_ROW_AGE))
+    }
+
+    fn seat_type_allowed(&self, seat_id: &str) -> bool {
+        let seat_type = match seat_id.chars().last().and_then(SeatType::from_char) {
+            Some(seat_type) => seat_type,
+            None => return false,
+        };
+        let (class_index, _) = match self.get_seat_class(seat_id) {
+            Some(seat_class) => seat_class,
+            None => return false,
+        };
+        self.seat_classes[class_index as usize - 1]
+            .allowed_seat_types
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&seat_type))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DiscountType {
+    PromoCode,
+    FirstFlyer,
 }
 
+#[derive(Clone)]
 struct Reservation {
     reservation_id: u32,
     user_id: String,
+    passenger_name: String,
     date: String,
     flight_id: u32,
     seat_id: String,
-    price: u32,
+    base_price: u32,
+    service_fee: u32,
+    total_price: u32,
     is_cancelled: bool,
+    is_checked_in: bool,
+    is_timeout_cancelled: bool,
+    promo_code: Option<String>,
+    preferred_seat_types: Option<Vec<SeatType>>,
+    agent_id: Option<String>,
+    commission: u32,
+    passenger_age: Option<u32>,
+    needs_assistance: bool,
+    original_class_index: Option<usize>,
+    discount_type: Option<DiscountType>,
+    name_change_used: bool,
+    pending_fare_adjustment: i64,
+    payment_method: Option<String>,
+    group_discount: u32,
+    fare_discount: u32,
+    booked_on: Option<NaiveDate>,
+    parent_reservation_id: Option<u32>,
+    is_rerouted: bool,
+    original_price: Option<u32>,
 }
 
 impl Reservation {
@@ -105,328 +613,9911 @@ impl Reservation {
     ) -> Self {
         Reservation {
             reservation_id,
+            passenger_name: user_id.clone(),
             user_id,
             date,
             flight_id,
             seat_id,
-            price,
+            base_price: price,
+            service_fee: 0,
+            total_price: price,
             is_cancelled: false,
+            is_checked_in: false,
+            is_timeout_cancelled: false,
+            promo_code: None,
+            preferred_seat_types: None,
+            agent_id: None,
+            commission: 0,
+            passenger_age: None,
+            needs_assistance: false,
+            original_class_index: None,
+            discount_type: None,
+            name_change_used: false,
+            pending_fare_adjustment: 0,
+            payment_method: None,
+            group_discount: 0,
+            fare_discount: 0,
+            booked_on: None,
+            parent_reservation_id: None,
+            is_rerouted: false,
+            original_price: None,
         }
     }
 }
 
-struct ReservationSystem {
-    flights: HashMap<u32, Flight>,
-    reservations: HashMap<u32, Reservation>,
-    seat_reservations: HashMap<String, HashMap<u32, HashMap<String, bool>>>,
-    next_reservation_id: u32,
+#[derive(Clone)]
+struct Lcg {
+    state: u64,
 }
 
-impl ReservationSystem {
-    fn new() -> Self {
-        ReservationSystem {
-            flights: HashMap::new(),
-            reservations: HashMap::new(),
-            seat_reservations: HashMap::new(),
-            next_reservation_id: 1,
-        }
+impl Lcg {
+    fn new(seed: u64) -> Self {
+        Lcg { state: seed }
     }
 
-    fn add_flight(
-        &mut self,
-        flight_id: u32,
-        departure_airport: u32,
-        arrival_airport: u32,
-        departure_time: String,
-        arrival_time: String,
-        seat_classes: Vec<SeatClass>,
-    ) {
-        let flight = Flight::new(
-            flight_id,
-            departure_airport,
-            arrival_airport,
-            departure_time,
-            arrival_time,
-            seat_classes,
-        );
-        self.flights.insert(flight_id, flight);
+    fn next_u64(&mut self) -> u64 {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.state >> 33
     }
 
-    fn parse_datetime(&self, date: &str, time: &str) -> Option<NaiveDateTime> {
-        let datetime_str = format!("{}-{}", date, time);
-        NaiveDateTime::parse_from_str(&datetime_str, "%Y/%m/%d-%H:%M:%S").ok()
+    fn next_below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
     }
+}
 
-    fn is_too_late(&self, current_datetime: NaiveDateTime, flight_datetime: NaiveDateTime) -> bool {
-        current_datetime >= flight_datetime - Duration::hours(2)
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PromoDiscount {
+    Percent(u32),
+    Fixed(u32),
+}
+
+impl PromoDiscount {
+    fn apply(&self, price: u32) -> u32 {
+        match self {
+            PromoDiscount::Percent(pct) => price - price * pct / 100,
+            PromoDiscount::Fixed(amount) => price.saturating_sub(*amount),
+        }
     }
+}
 
-    fn get_flight_datetime(&self, date: &str, flight: &Flight) -> Option<NaiveDateTime> {
-        self.parse_datetime(date, &flight.departure_time)
+#[derive(Clone)]
+struct PromoCode {
+    discount: PromoDiscount,
+    max_uses: u32,
+    uses: u32,
+    expiry_date: NaiveDate,
+}
+
+#[derive(Clone, Copy)]
+enum ReservationField {
+    ReservationId,
+    Price,
+    Date,
+    Flight,
+    Seat,
+    Route,
+}
+
+impl ReservationField {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "reservation_id" => Some(ReservationField::ReservationId),
+            "price" => Some(ReservationField::Price),
+            "date" => Some(ReservationField::Date),
+            "flight" => Some(ReservationField::Flight),
+            "seat" => Some(ReservationField::Seat),
+            "route" => Some(ReservationField::Route),
+            _ => None,
+        }
     }
 
-    fn is_seat_reserved(&self, date: &str, flight_id: u32, seat_id: &str) -> bool {
-        if let Some(flights_on_date) = self.seat_reservations.get(date) {
-            if let Some(seats) = flights_on_date.get(&flight_id) {
-                if let Some(&reserved) = seats.get(seat_id) {
-                    return reserved;
-                }
+    fn render(&self, reservation: &Reservation, flight: &Flight) -> String {
+        match self {
+            ReservationField::ReservationId => {
+                format!("reservation id: {}", reservation.reservation_id)
             }
+            ReservationField::Price => format!("price: {}", reservation.total_price),
+            ReservationField::Date => format!("date: {}", reservation.date),
+            ReservationField::Flight => format!("flight: {}", reservation.flight_id),
+            ReservationField::Seat => format!("seat: {}", reservation.seat_id),
+            ReservationField::Route => format!(
+                "route: {} ({}) -> {} ({})",
+                flight.departure_airport,
+                flight.departure_time,
+                flight.arrival_airport,
+                flight.arrival_time
+            ),
         }
-        false
     }
+}
 
-    fn reserve_seat(&mut self, date: &str, flight_id: u32, seat_id: &str) {
-        self.seat_reservations
-            .entry(date.to_string())
-            .or_insert_with(HashMap::new)
-            .entry(flight_id)
-            .or_insert_with(HashMap::new)
-            .insert(seat_id.to_string(), true);
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CoverageType {
+    CancellationCover,
+    DelayCompensation,
+    FullCover,
+}
+
+impl CoverageType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cancellation" => Some(CoverageType::CancellationCover),
+            "delay" => Some(CoverageType::DelayCompensation),
+            "full" => Some(CoverageType::FullCover),
+            _ => None,
+        }
     }
 
-    fn unreserve_seat(&mut self, date: &str, flight_id: u32, seat_id: &str) {
-        if let Some(flights_on_date) = self.seat_reservations.get_mut(date) {
-            if let Some(seats) = flights_on_date.get_mut(&flight_id) {
-                seats.insert(seat_id.to_string(), false);
-            }
+    fn premium_percent(&self) -> u32 {
+        match self {
+            CoverageType::CancellationCover => 5,
+            CoverageType::DelayCompensation => 3,
+            CoverageType::FullCover => 8,
         }
     }
 
-    fn process_reserve(
-        &mut self,
-        current_datetime: &str,
-        user_id: &str,
-        date: &str,
-        flight_id: u32,
-        seat_id: &str,
-    ) -> String {
-        if !self.flights.contains_key(&flight_id) {
-            return "reserve: flight not found".to_string();
+    fn coverage_amount(&self, price: u32) -> u32 {
+        match self {
+            CoverageType::CancellationCover | CoverageType::FullCover => price,
+            CoverageType::DelayCompensation => price / 4,
         }
+    }
+}
 
-        let flight = self.flights.get(&flight_id).unwrap();
-        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
-            Ok(dt) => dt,
-            Err(_) => return "reserve: invalid datetime".to_string(),
+impl fmt::Display for CoverageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CoverageType::CancellationCover => "cancellation",
+            CoverageType::DelayCompensation => "delay",
+            CoverageType::FullCover => "full",
         };
+        write!(f, "{}", name)
+    }
+}
 
-        let flight_dt = match self.get_flight_datetime(date, flight) {
-            Some(dt) => dt,
-            None => return "reserve: invalid flight datetime".to_string(),
-        };
+struct InsurancePolicy {
+    policy_id: u32,
+    reservation_id: u32,
+    coverage_type: CoverageType,
+    premium: u32,
+    is_claimed: bool,
+}
 
-        if self.is_too_late(current_dt, flight_dt) {
-            return "reserve: too late".to_string();
-        }
+struct RefundVoucher {
+    voucher_id: u32,
+    user_id: String,
+    reservation_id: u32,
+    amount: u32,
+}
 
-        if self.is_seat_reserved(date, flight_id, seat_id) {
-            return "reserve: already reserved".to_string();
-        }
+struct AlertPreference {
+    user_id: String,
+    confirmation: bool,
+    reminder_hours_before: Vec<u32>,
+    cancellation_notice: bool,
+}
 
-        let (_, price) = match flight.get_seat_class(seat_id) {
-            Some((sc, pr)) => (sc, pr),
-            None => return "reserve: invalid seat_id".to_string(),
-        };
+#[derive(Default)]
+struct UserProfile {
+    preferred_seat_types: Vec<SeatType>,
+    passenger_name: Option<String>,
+    age: Option<u32>,
+    email: Option<String>,
+    corporate_account_id: Option<String>,
+}
 
-        let reservation = Reservation::new(
-            self.next_reservation_id,
-            user_id.to_string(),
-            date.to_string(),
-            flight_id,
-            seat_id.to_string(),
-            price,
+struct GroupPricingRule {
+    rule_id: u32,
+    flight_id: Option<u32>,
+    class_index: Option<usize>,
+    buy_n: u32,
+    free_m: u32,
+}
+
+struct UserWallet {
+    user_id: String,
+    balances: HashMap<String, u32>,
+}
+
+#[derive(Clone, Copy)]
+struct RateLimitConfig {
+    max_requests: u32,
+    window_seconds: u64,
+}
+
+struct CapacityAlert {
+    alert_id: u32,
+    flight_id: u32,
+    class_index: usize,
+    threshold_pct: u32,
+    triggered: bool,
+}
+
+struct MilesEntry {
+    amount: u32,
+    earned_at: NaiveDate,
+    source_reservation_id: u32,
+}
+
+#[derive(Default)]
+struct MilesLedger {
+    entries: Vec<MilesEntry>,
+    expired: u32,
+}
+
+impl MilesLedger {
+    fn is_active(entry: &MilesEntry, current_date: NaiveDate) -> bool {
+        entry
+            .earned_at
+            .checked_add_months(Months::new(MILES_VALIDITY_MONTHS))
+            .is_some_and(|expires_on| expires_on > current_date)
+    }
+
+    fn active_balance(&self, current_date: NaiveDate) -> u32 {
+        self.entries
+            .iter()
+            .filter(|entry| Self::is_active(entry, current_date))
+            .map(|entry| entry.amount)
+            .sum()
+    }
+
+    fn total_earned(&self) -> u32 {
+        self.entries.iter().map(|entry| entry.amount).sum::<u32>() + self.expired
+    }
+
+    fn earned_from(&self, reservation_id: u32) -> u32 {
+        self.entries
+            .iter()
+            .filter(|entry| entry.source_reservation_id == reservation_id)
+            .map(|entry| entry.amount)
+            .sum()
+    }
+
+    fn expire(&mut self, current_date: NaiveDate) -> u32 {
+        let before = self.entries.iter().map(|entry| entry.amount).sum::<u32>();
+        self.entries.retain(|entry| Self::is_active(entry, current_date));
+        let expired = before - self.entries.iter().map(|entry| entry.amount).sum::<u32>();
+        self.expired += expired;
+        expired
+    }
+}
+
+struct Itinerary {
+    itinerary_id: u32,
+    reservation_ids: Vec<u32>,
+    user_id: String,
+}
+
+#[derive(Clone)]
+struct PriceHistoryEntry {
+    timestamp: String,
+    date: String,
+    flight_id: u32,
+    class_index: usize,
+    price: u32,
+    occupancy_pct: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SwapStatus {
+    Pending,
+    Accepted,
+    Rejected,
+    Expired,
+}
+
+struct SwapRequest {
+    request_id: u32,
+    initiator_user_id: String,
+    initiator_reservation_id: u32,
+    target_user_id: String,
+    target_reservation_id: u32,
+    expires_at: NaiveDateTime,
+    status: SwapStatus,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TaskType {
+    PurgeLocks,
+    AutoCancelNoCheckin,
+    PruneWaitlist,
+}
+
+impl TaskType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "purge-locks" => Some(TaskType::PurgeLocks),
+            "auto-cancel-no-checkin" => Some(TaskType::AutoCancelNoCheckin),
+            "prune-waitlist" => Some(TaskType::PruneWaitlist),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TaskType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TaskType::PurgeLocks => "purge-locks",
+            TaskType::AutoCancelNoCheckin => "auto-cancel-no-checkin",
+            TaskType::PruneWaitlist => "prune-waitlist",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+struct ScheduledTask {
+    task_id: u32,
+    trigger_every_minutes: u32,
+    last_run: Option<NaiveDateTime>,
+    task_type: TaskType,
+}
+
+struct WaitlistEntry {
+    user_id: String,
+    expires_at: NaiveDateTime,
+}
+
+struct SeatLock {
+    user_id: String,
+    date: String,
+    flight_id: u32,
+    seat_id: String,
+    expires_at: NaiveDateTime,
+}
+
+struct DebitEntry {
+    user_id: String,
+    amount: u32,
+    reason: String,
+    date: NaiveDate,
+    is_settled: bool,
+}
+
+struct FlightReview {
+    review_id: u32,
+    reservation_id: u32,
+    user_id: String,
+    flight_id: u32,
+    date: String,
+    stars: u8,
+    comment: String,
+}
+
+struct KioskSession {
+    token: String,
+    user_id: String,
+    started_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+}
+
+struct CheckinQueueEntry {
+    entry_id: u32,
+    user_id: String,
+    flight_id: u32,
+    date: String,
+    joined_at: NaiveDateTime,
+}
+
+struct AuditEntry {
+    entry_id: u32,
+    timestamp: NaiveDateTime,
+    actor: String,
+    operation: String,
+    details: String,
+}
+
+struct AuctionSeat {
+    date: String,
+    flight_id: u32,
+    seat_id: String,
+    bids: Vec<(String, u32)>,
+    ends_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SeatEventType {
+    Reserved,
+    Freed,
+    Blocked,
+}
+
+impl fmt::Display for SeatEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeatEventType::Reserved => write!(f, "reserved"),
+            SeatEventType::Freed => write!(f, "freed"),
+            SeatEventType::Blocked => write!(f, "blocked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SeatEvent {
+    event_type: SeatEventType,
+    date: String,
+    flight_id: u32,
+    seat_id: String,
+}
+
+struct SeatEventReceiver {
+    receiver: Receiver<SeatEvent>,
+}
+
+impl SeatEventReceiver {
+    fn try_recv(&self) -> Option<SeatEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+trait NotificationHook {
+    fn on_reservation_created(&self, r: &Reservation);
+    fn on_reservation_cancelled(&self, r: &Reservation);
+    fn on_flight_delayed(&self, flight_id: u32, new_departure: &str);
+}
+
+struct LoggingHook;
+
+impl NotificationHook for LoggingHook {
+    fn on_reservation_created(&self, r: &Reservation) {
+        eprintln!(
+            "notification: reservation-created {} {} {} {} {}",
+            r.reservation_id, r.user_id, r.date, r.flight_id, r.seat_id
         );
-        self.reservations.insert(self.next_reservation_id, reservation);
-        self.reserve_seat(date, flight_id, seat_id);
+    }
 
-        let result = format!("reserve: {} {}", self.next_reservation_id, price);
-        self.next_reservation_id += 1;
-        result
+    fn on_reservation_cancelled(&self, r: &Reservation) {
+        eprintln!(
+            "notification: reservation-cancelled {} {} {} {} {}",
+            r.reservation_id, r.user_id, r.date, r.flight_id, r.seat_id
+        );
     }
 
-    fn process_cancel(
-        &mut self,
-        current_datetime: &str,
-        user_id: &str,
+    fn on_flight_delayed(&self, flight_id: u32, new_departure: &str) {
+        eprintln!("notification: flight-delayed {} {}", flight_id, new_departure);
+    }
+}
+
+#[derive(Clone)]
+enum SystemEvent {
+    FlightAdded(Flight),
+    ReservationCreated {
         reservation_id: u32,
-    ) -> String {
-        {
-            let reservation = match self.reservations.get(&reservation_id) {
-                Some(reservation) => reservation,
-                None => return "cancel: reservation not found".to_string(),
-            };
+        user_id: String,
+        date: String,
+        flight_id: u32,
+        seat_id: String,
+        price: u32,
+    },
+    SeatReserved {
+        date: String,
+        flight_id: u32,
+        seat_id: String,
+    },
+    SeatFreed {
+        date: String,
+        flight_id: u32,
+        seat_id: String,
+    },
+    ReservationCancelled {
+        reservation_id: u32,
+    },
+}
 
-            if reservation.is_cancelled {
-                return "cancel: reservation not found".to_string();
+impl SystemEvent {
+    fn to_json(&self) -> JsonValue {
+        let text = |value: &str| JsonValue::String(value.to_string());
+        let number = |value: u32| JsonValue::Number(value as f64);
+        let entries = match self {
+            SystemEvent::FlightAdded(flight) => vec![
+                ("type", text("flight_added")),
+                ("flight_id", number(flight.flight_id)),
+                ("departure_airport", number(flight.departure_airport)),
+                ("arrival_airport", number(flight.arrival_airport)),
+            ],
+            SystemEvent::ReservationCreated {
+                reservation_id,
+                user_id,
+                date,
+                flight_id,
+                seat_id,
+                price,
+            } => vec![
+                ("type", text("reservation_created")),
+                ("reservation_id", number(*reservation_id)),
+                ("user_id", text(user_id)),
+                ("date", text(date)),
+                ("flight_id", number(*flight_id)),
+                ("seat_id", text(seat_id)),
+                ("price", number(*price)),
+            ],
+            SystemEvent::SeatReserved {
+                date,
+                flight_id,
+                seat_id,
+            } => vec![
+                ("type", text("seat_reserved")),
+                ("date", text(date)),
+                ("flight_id", number(*flight_id)),
+                ("seat_id", text(seat_id)),
+            ],
+            SystemEvent::SeatFreed {
+                date,
+                flight_id,
+                seat_id,
+            } => vec![
+                ("type", text("seat_freed")),
+                ("date", text(date)),
+                ("flight_id", number(*flight_id)),
+                ("seat_id", text(seat_id)),
+            ],
+            SystemEvent::ReservationCancelled { reservation_id } => vec![
+                ("type", text("reservation_cancelled")),
+                ("reservation_id", number(*reservation_id)),
+            ],
+        };
+        JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Default)]
+struct SearchCache {
+    entries: HashMap<(String, String, u32, u32), (String, NaiveDateTime)>,
+}
+
+#[derive(Default)]
+struct EventStore {
+    events: Vec<SystemEvent>,
+}
+
+enum ReservationError {
+    Rejected(&'static str),
+    Inconsistent,
+}
+
+struct Checkpoint {
+    reservation_id: u32,
+    next_reservation_id: u32,
+    seat: (String, u32, String),
+    seat_state: Option<SeatState>,
+    promo_uses: Option<(String, u32)>,
+    price_history_len: usize,
+    event_count: Option<usize>,
+}
+
+enum QueryError {
+    UnknownCommand,
+    InvalidQuery,
+}
+
+#[derive(Default)]
+struct ReserveOptions {
+    promo_code: Option<String>,
+    agent_id: Option<String>,
+    passenger_age: Option<u32>,
+    needs_assistance: bool,
+    idem_key: Option<String>,
+}
+
+impl ReserveOptions {
+    fn parse(tokens: &[&str]) -> Result<Self, QueryError> {
+        let mut options = ReserveOptions::default();
+        for token in tokens {
+            match token.split_once('=') {
+                Some(("promo", code)) if !code.is_empty() => {
+                    options.promo_code = Some(code.to_string())
+                }
+                Some(("agent", agent_id)) if !agent_id.is_empty() => {
+                    options.agent_id = Some(agent_id.to_string())
+                }
+                Some(("age", age)) => options.passenger_age = Some(parse_arg(age)?),
+                Some(("assist", needs_assistance)) => {
+                    options.needs_assistance = parse_arg(needs_assistance)?
+                }
+                Some(("idem", key)) if !key.is_empty() => options.idem_key = Some(key.to_string()),
+                _ => return Err(QueryError::InvalidQuery),
             }
+        }
+        Ok(options)
+    }
+}
 
-            if reservation.user_id != user_id {
-                return "cancel: unauthorized operation".to_string();
+fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+fn fnv1a_64(data: &str) -> u64 {
+    data.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Clone)]
+struct RecordedCommand {
+    command: String,
+    args: Vec<String>,
+    payload: Vec<String>,
+}
+
+impl RecordedCommand {
+    fn query_line(&self) -> String {
+        let mut tokens = vec![self.command.as_str()];
+        tokens.extend(self.args.iter().map(String::as_str));
+        tokens.join(" ")
+    }
+
+    fn to_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.query_line()];
+        lines.extend(self.payload.iter().map(|line| format!("  {}", line)));
+        lines
+    }
+
+    fn parse_log(lines: &[String]) -> Vec<RecordedCommand> {
+        let mut log: Vec<RecordedCommand> = vec![];
+        for line in lines {
+            match (line.strip_prefix("  "), log.last_mut()) {
+                (Some(payload_line), Some(recorded)) => {
+                    recorded.payload.push(payload_line.to_string())
+                }
+                _ => {
+                    let mut tokens = line.split_whitespace().map(|token| token.to_string());
+                    if let Some(command) = tokens.next() {
+                        log.push(RecordedCommand {
+                            command,
+                            args: tokens.collect(),
+                            payload: vec![],
+                        });
+                    }
+                }
             }
+        }
+        log
+    }
+}
 
-            let flight = self.flights.get(&reservation.flight_id).unwrap();
-            let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S")
-            {
-                Ok(dt) => dt,
-                Err(_) => return "cancel: invalid datetime".to_string(),
-            };
+struct MessageCatalog {
+    lang: String,
+    messages: HashMap<&'static str, String>,
+}
 
-            let flight_dt = match self.get_flight_datetime(&reservation.date, flight) {
-                Some(dt) => dt,
-                None => return "cancel: invalid flight datetime".to_string(),
+impl MessageCatalog {
+    fn builtin() -> Self {
+        let messages = DEFAULT_MESSAGES
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, message)| (key.trim(), message.to_string()))
+            .collect();
+        MessageCatalog {
+            lang: DEFAULT_LANGUAGE.to_string(),
+            messages,
+        }
+    }
+
+    fn parse(lang: &str, text: &str, builtin: &MessageCatalog) -> Result<Self, String> {
+        let mut messages = HashMap::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, message) = match line.split_once('=') {
+                Some((key, message)) => (key.trim(), message),
+                None => return Err(format!("line {}: expected key=value", line_no + 1)),
+            };
+            let key = match builtin.messages.get_key_value(key) {
+                Some((key, _)) => *key,
+                None => return Err(format!("line {}: unknown message key {}", line_no + 1, key)),
             };
+            messages.insert(key, message.to_string());
+        }
+        Ok(MessageCatalog {
+            lang: lang.to_string(),
+            messages,
+        })
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: &str) -> Result<T, QueryError> {
+    arg.parse().map_err(|_| QueryError::InvalidQuery)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn closest_command<'a>(input: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|command| (*command, levenshtein(input, command)))
+        .min_by_key(|(_, distance)| *distance)
+        .filter(|(_, distance)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .map(|(command, _)| command)
+}
+
+struct ReservationSystem {
+    flights: HashMap<u32, Flight>,
+    reservations: HashMap<u32, Reservation>,
+    seat_reservations: HashMap<String, HashMap<u32, HashMap<String, SeatState>>>,
+    next_lock_id: u64,
+    next_reservation_id: u32,
+    admin_token: String,
+    blocked_users: HashSet<String>,
+    active_auctions: HashMap<String, AuctionSeat>,
+    auction_notices: HashMap<String, Vec<String>>,
+    alert_prefs: HashMap<String, AlertPreference>,
+    failed_commands: Vec<(u32, String)>,
+    query_count: u32,
+    seat_event_senders: Vec<Sender<SeatEvent>>,
+    vouchers: HashMap<u32, RefundVoucher>,
+    next_voucher_id: u32,
+    itineraries: HashMap<u32, Itinerary>,
+    next_itinerary_id: u32,
+    min_connection_times: HashMap<u32, u32>,
+    rng: Lcg,
+    promo_codes: HashMap<String, PromoCode>,
+    insurance_policies: HashMap<u32, InsurancePolicy>,
+    next_policy_id: u32,
+    user_profiles: HashMap<String, UserProfile>,
+    corporate_accounts: HashMap<String, Vec<String>>,
+    manifest_timestamps: HashMap<(u32, String), NaiveDateTime>,
+    catalogs: HashMap<String, MessageCatalog>,
+    language: String,
+    recording: bool,
+    command_log: Vec<RecordedCommand>,
+    query_depth: u32,
+    clock: NaiveDateTime,
+    audit_log: Vec<AuditEntry>,
+    price_history: Vec<PriceHistoryEntry>,
+    miles: HashMap<String, MilesLedger>,
+    commission_rates: HashMap<String, u32>,
+    rebook_protection: bool,
+    active_kiosk_sessions: HashMap<u32, KioskSession>,
+    next_kiosk_session_id: u32,
+    checkin_queue: VecDeque<CheckinQueueEntry>,
+    next_checkin_queue_entry_id: u32,
+    service_fee: u32,
+    fee_refundable: bool,
+    reviews: Vec<FlightReview>,
+    seat_locks: HashMap<u64, SeatLock>,
+    first_flyer_discount_pct: u32,
+    waitlists: HashMap<(String, u32, u32), Vec<WaitlistEntry>>,
+    waitlist_max_retry: u32,
+    event_store: Option<EventStore>,
+    state_journal: Vec<RecordedCommand>,
+    checkpoint_every: Option<u32>,
+    checkpoint_path: String,
+    processed_query_count: u32,
+    swap_requests: HashMap<u32, SwapRequest>,
+    output_delimiter: char,
+    scheduled_tasks: Vec<ScheduledTask>,
+    search_cache: SearchCache,
+    cache_ttl_seconds: u64,
+    wallets: HashMap<String, UserWallet>,
+    exchange_rates: HashMap<String, u32>,
+    seat_weights: Vec<SeatTypeWeight>,
+    group_rules: Vec<GroupPricingRule>,
+    idempotency_keys: HashMap<String, (u32, NaiveDateTime)>,
+    hooks: Vec<Box<dyn NotificationHook>>,
+    max_reservations_per_user_per_day: Option<u32>,
+    compensated_flights: HashSet<(u32, String)>,
+    peak_dates: HashSet<NaiveDate>,
+    capacity_alerts: Vec<CapacityAlert>,
+    test_data_seed: Option<u64>,
+    rate_limit_config: Option<RateLimitConfig>,
+    rate_limit_counters: HashMap<String, VecDeque<NaiveDateTime>>,
+    lock_expiry_fee: u32,
+    debit_log: Vec<DebitEntry>,
+}
+
+impl ReservationSystem {
+    fn new() -> Self {
+        ReservationSystem {
+            flights: HashMap::new(),
+            reservations: HashMap::new(),
+            seat_reservations: HashMap::new(),
+            next_lock_id: 1,
+            next_reservation_id: 1,
+            admin_token: DEFAULT_ADMIN_TOKEN.to_string(),
+            blocked_users: HashSet::new(),
+            active_auctions: HashMap::new(),
+            auction_notices: HashMap::new(),
+            alert_prefs: HashMap::new(),
+            failed_commands: vec![],
+            query_count: 0,
+            seat_event_senders: vec![],
+            vouchers: HashMap::new(),
+            next_voucher_id: 1,
+            itineraries: HashMap::new(),
+            next_itinerary_id: 1,
+            min_connection_times: HashMap::new(),
+            rng: Lcg::new(DEFAULT_RNG_SEED),
+            promo_codes: HashMap::new(),
+            insurance_policies: HashMap::new(),
+            next_policy_id: 1,
+            user_profiles: HashMap::new(),
+            corporate_accounts: HashMap::new(),
+            manifest_timestamps: HashMap::new(),
+            catalogs: HashMap::from([(DEFAULT_LANGUAGE.to_string(), MessageCatalog::builtin())]),
+            language: DEFAULT_LANGUAGE.to_string(),
+            recording: false,
+            command_log: vec![],
+            query_depth: 0,
+            clock: NaiveDateTime::default(),
+            audit_log: vec![],
+            price_history: vec![],
+            miles: HashMap::new(),
+            commission_rates: HashMap::new(),
+            rebook_protection: false,
+            active_kiosk_sessions: HashMap::new(),
+            next_kiosk_session_id: 1,
+            checkin_queue: VecDeque::new(),
+            next_checkin_queue_entry_id: 1,
+            service_fee: 0,
+            fee_refundable: false,
+            reviews: vec![],
+            seat_locks: HashMap::new(),
+            first_flyer_discount_pct: DEFAULT_FIRST_FLYER_DISCOUNT_PCT,
+            waitlists: HashMap::new(),
+            waitlist_max_retry: DEFAULT_WAITLIST_MAX_RETRY,
+            event_store: None,
+            state_journal: vec![],
+            checkpoint_every: None,
+            checkpoint_path: String::new(),
+            processed_query_count: 0,
+            swap_requests: HashMap::new(),
+            output_delimiter: ' ',
+            scheduled_tasks: vec![],
+            search_cache: SearchCache::default(),
+            cache_ttl_seconds: 0,
+            wallets: HashMap::new(),
+            exchange_rates: HashMap::from([(BASE_CURRENCY.to_string(), 1)]),
+            seat_weights: SeatTypeWeight::defaults(),
+            group_rules: vec![],
+            idempotency_keys: HashMap::new(),
+            hooks: vec![],
+            max_reservations_per_user_per_day: None,
+            compensated_flights: HashSet::new(),
+            peak_dates: HashSet::new(),
+            capacity_alerts: vec![],
+            test_data_seed: None,
+            rate_limit_config: None,
+            rate_limit_counters: HashMap::new(),
+            lock_expiry_fee: 0,
+            debit_log: Vec::new(),
+        }
+    }
+
+    fn add_flight(&mut self, mut flight: Flight) {
+        flight.sort_seat_classes();
+        self.record_event(|| SystemEvent::FlightAdded(flight.clone()));
+        self.flights.insert(flight.flight_id, flight);
+        self.clear_search_cache();
+    }
+
+    fn enable_event_sourcing(&mut self) {
+        let mut flight_ids: Vec<u32> = self.flights.keys().copied().collect();
+        flight_ids.sort();
+        let events = flight_ids
+            .into_iter()
+            .map(|flight_id| SystemEvent::FlightAdded(self.flights[&flight_id].clone()))
+            .collect();
+        self.event_store = Some(EventStore { events });
+    }
+
+    fn record_event(&mut self, event: impl FnOnce() -> SystemEvent) {
+        if let Some(store) = &mut self.event_store {
+            store.events.push(event());
+        }
+    }
+
+    fn rebuild_from_events(events: &[SystemEvent]) -> ReservationSystem {
+        let mut system = ReservationSystem::new();
+        system.event_store = Some(EventStore::default());
+        for event in events {
+            match event {
+                SystemEvent::FlightAdded(flight) => system.add_flight(flight.clone()),
+                SystemEvent::ReservationCreated {
+                    reservation_id,
+                    user_id,
+                    date,
+                    flight_id,
+                    seat_id,
+                    price,
+                } => {
+                    system.reservations.insert(
+                        *reservation_id,
+                        Reservation::new(
+                            *reservation_id,
+                            user_id.clone(),
+                            date.clone(),
+                            *flight_id,
+                            seat_id.clone(),
+                            *price,
+                        ),
+                    );
+                    system.next_reservation_id = system.next_reservation_id.max(reservation_id + 1);
+                    system.record_event(|| event.clone());
+                }
+                SystemEvent::SeatReserved {
+                    date,
+                    flight_id,
+                    seat_id,
+                } => system.reserve_seat(date, *flight_id, seat_id),
+                SystemEvent::SeatFreed {
+                    date,
+                    flight_id,
+                    seat_id,
+                } => system.unreserve_seat(date, *flight_id, seat_id),
+                SystemEvent::ReservationCancelled { reservation_id } => {
+                    if let Some(reservation) = system.reservations.get_mut(reservation_id) {
+                        reservation.is_cancelled = true;
+                    }
+                    system.record_event(|| event.clone());
+                }
+            }
+        }
+        system
+    }
+
+    fn parse_datetime(&self, date: &str, time: &str) -> Option<NaiveDateTime> {
+        let datetime_str = format!("{}-{}", date, time);
+        NaiveDateTime::parse_from_str(&datetime_str, "%Y/%m/%d-%H:%M:%S").ok()
+    }
+
+    fn parse_date(&self, date: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(date, "%Y/%m/%d").ok()
+    }
+
+    fn is_too_late(&self, current_datetime: NaiveDateTime, flight_datetime: NaiveDateTime) -> bool {
+        current_datetime >= flight_datetime - Duration::hours(2)
+    }
+
+    fn get_flight_datetime(&self, date: &str, flight: &Flight) -> Option<NaiveDateTime> {
+        self.parse_datetime(date, &flight.departure_time)
+    }
+
+    fn seat_key(&self, date: &str, flight_id: u32, seat_id: &str) -> String {
+        format!("{} {} {}", date, flight_id, seat_id)
+    }
+
+    fn get_arrival_datetime(&self, date: &str, flight: &Flight) -> Option<NaiveDateTime> {
+        let departure = self.parse_datetime(date, &flight.departure_time)?;
+        let arrival = self.parse_datetime(date, &flight.arrival_time)?;
+        if arrival < departure {
+            Some(arrival + Duration::days(1))
+        } else {
+            Some(arrival)
+        }
+    }
+
+    fn min_connection_time(&self, airport: u32) -> Duration {
+        let minutes = self
+            .min_connection_times
+            .get(&airport)
+            .copied()
+            .unwrap_or(DEFAULT_MIN_CONNECTION_MINUTES);
+        Duration::minutes(minutes as i64)
+    }
+
+    fn load_catalog(&mut self, path: &str) -> Result<String, String> {
+        let lang = std::path::Path::new(path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| format!("{}: invalid catalog file name", path))?
+            .to_string();
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let catalog = MessageCatalog::parse(&lang, &text, &self.catalogs[DEFAULT_LANGUAGE])
+            .map_err(|err| format!("{}: {}", path, err))?;
+        self.catalogs.insert(catalog.lang.clone(), catalog);
+        Ok(lang)
+    }
+
+    fn set_language(&mut self, lang: &str) -> Result<(), String> {
+        if !self.catalogs.contains_key(lang) {
+            return Err(format!("unknown language: {}", lang));
+        }
+        self.language = lang.to_string();
+        self.clear_search_cache();
+        Ok(())
+    }
+
+    fn msg(&self, key: &str) -> String {
+        [self.language.as_str(), DEFAULT_LANGUAGE]
+            .iter()
+            .find_map(|lang| self.catalogs.get(*lang)?.messages.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    fn message_key(&self, message: &str) -> Option<&'static str> {
+        [self.language.as_str(), DEFAULT_LANGUAGE]
+            .iter()
+            .filter_map(|lang| self.catalogs.get(*lang))
+            .find_map(|catalog| {
+                catalog
+                    .messages
+                    .iter()
+                    .find(|(_, text)| text.as_str() == message)
+                    .map(|(&key, _)| key)
+            })
+    }
+
+    fn msg_fmt(&self, key: &str, args: &[&dyn fmt::Display]) -> String {
+        let template = self.msg(key);
+        let mut pieces = template.split("{}");
+        let mut result = pieces.next().unwrap_or_default().to_string();
+        for (i, piece) in pieces.enumerate() {
+            if let Some(arg) = args.get(i) {
+                result.push_str(&arg.to_string());
+            }
+            result.push_str(piece);
+        }
+        result
+    }
+
+    fn record_audit(&mut self, actor: &str, operation: &str, details: String) {
+        self.audit_log.push(AuditEntry {
+            entry_id: self.audit_log.len() as u32 + 1,
+            timestamp: self.clock,
+            actor: actor.to_string(),
+            operation: operation.to_string(),
+            details,
+        });
+    }
+
+    fn is_admin(&self, admin_token: &str) -> bool {
+        self.admin_token == admin_token
+    }
+
+    fn seat_state(&self, date: &str, flight_id: u32, seat_id: &str) -> SeatState {
+        if let Some(flights_on_date) = self.seat_reservations.get(date) {
+            if let Some(seats) = flights_on_date.get(&flight_id) {
+                if let Some(&state) = seats.get(seat_id) {
+                    return match state {
+                        SeatState::TentativeLock(lock_id)
+                            if self
+                                .seat_locks
+                                .get(&lock_id)
+                                .is_some_and(|lock| lock.expires_at <= self.clock) =>
+                        {
+                            SeatState::Free
+                        }
+                        state => state,
+                    };
+                }
+            }
+        }
+        SeatState::Free
+    }
+
+    fn invalidate_search_cache(&mut self, date: &str, flight_id: u32) {
+        let (departure_airport, arrival_airport) = match self.flights.get(&flight_id) {
+            Some(flight) => (flight.departure_airport, flight.arrival_airport),
+            None => return,
+        };
+        self.search_cache.entries.retain(|(cached_date, _, dep, arr), _| {
+            (cached_date.as_str(), *dep, *arr) != (date, departure_airport, arrival_airport)
+        });
+    }
+
+    fn clear_search_cache(&mut self) {
+        self.search_cache.entries.clear();
+    }
+
+    fn set_seat_state(&mut self, date: &str, flight_id: u32, seat_id: &str, state: SeatState) {
+        self.invalidate_search_cache(date, flight_id);
+        self.seat_reservations
+            .entry(date.to_string())
+            .or_default()
+            .entry(flight_id)
+            .or_default()
+            .insert(seat_id.to_string(), state);
+    }
+
+    fn is_seat_reserved(&self, date: &str, flight_id: u32, seat_id: &str) -> bool {
+        self.seat_state(date, flight_id, seat_id) != SeatState::Free
+    }
+
+    fn register_hook(&mut self, hook: Box<dyn NotificationHook>) {
+        self.hooks.push(hook);
+    }
+
+    fn notify_reservation_cancelled(&self, reservation_id: u32) {
+        let reservation = &self.reservations[&reservation_id];
+        self.hooks.iter().for_each(|h| h.on_reservation_cancelled(reservation));
+    }
+
+    fn subscribe(&mut self) -> SeatEventReceiver {
+        let (sender, receiver) = mpsc::channel();
+        self.seat_event_senders.push(sender);
+        SeatEventReceiver { receiver }
+    }
+
+    fn publish_seat_event(
+        &mut self,
+        event_type: SeatEventType,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+    ) {
+        let event = SeatEvent {
+            event_type,
+            date: date.to_string(),
+            flight_id,
+            seat_id: seat_id.to_string(),
+        };
+        self.seat_event_senders
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
+    fn reserve_seat(&mut self, date: &str, flight_id: u32, seat_id: &str) {
+        self.record_event(|| SystemEvent::SeatReserved {
+            date: date.to_string(),
+            flight_id,
+            seat_id: seat_id.to_string(),
+        });
+        self.set_seat_state(date, flight_id, seat_id, SeatState::Reserved);
+        self.publish_seat_event(SeatEventType::Reserved, date, flight_id, seat_id);
+    }
+
+    fn unreserve_seat(&mut self, date: &str, flight_id: u32, seat_id: &str) {
+        self.record_event(|| SystemEvent::SeatFreed {
+            date: date.to_string(),
+            flight_id,
+            seat_id: seat_id.to_string(),
+        });
+        if let Some(flights_on_date) = self.seat_reservations.get_mut(date) {
+            if let Some(seats) = flights_on_date.get_mut(&flight_id) {
+                seats.insert(seat_id.to_string(), SeatState::Free);
+            }
+        }
+        self.invalidate_search_cache(date, flight_id);
+        self.publish_seat_event(SeatEventType::Freed, date, flight_id, seat_id);
+    }
+
+    fn create_reservation(
+        &mut self,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+        price: u32,
+    ) -> u32 {
+        let reservation = self.new_reservation(user_id, date, flight_id, seat_id, price);
+        self.insert_reservation(reservation)
+    }
+
+    fn new_reservation(
+        &mut self,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+        price: u32,
+    ) -> Reservation {
+        while self.reservations.contains_key(&self.next_reservation_id) {
+            self.next_reservation_id += 1;
+        }
+        let reservation_id = self.next_reservation_id;
+        let mut reservation = Reservation::new(
+            reservation_id,
+            user_id.to_string(),
+            date.to_string(),
+            flight_id,
+            seat_id.to_string(),
+            price,
+        );
+        reservation.original_class_index = self
+            .flights
+            .get(&flight_id)
+            .and_then(|flight| flight.get_seat_class(seat_id))
+            .map(|(class_index, _)| class_index as usize);
+        reservation.preferred_seat_types = self
+            .user_profiles
+            .get(user_id)
+            .filter(|profile| !profile.preferred_seat_types.is_empty())
+            .map(|profile| profile.preferred_seat_types.clone());
+        if let Some(passenger_name) = self
+            .user_profiles
+            .get(user_id)
+            .and_then(|profile| profile.passenger_name.clone())
+        {
+            reservation.passenger_name = passenger_name;
+        }
+        reservation
+    }
+
+    fn insert_reservation(&mut self, reservation: Reservation) -> u32 {
+        let reservation_id = reservation.reservation_id;
+        let date = reservation.date.clone();
+        let flight_id = reservation.flight_id;
+        let seat_id = reservation.seat_id.clone();
+        let price = reservation.base_price;
+        self.record_event(|| SystemEvent::ReservationCreated {
+            reservation_id,
+            user_id: reservation.user_id.clone(),
+            date: date.clone(),
+            flight_id,
+            seat_id: seat_id.clone(),
+            price,
+        });
+        self.reservations.insert(reservation_id, reservation);
+        #[cfg(test)]
+        tests::fail_point("insert_reservation");
+        self.reserve_seat(&date, flight_id, &seat_id);
+        self.next_reservation_id += 1;
+        self.record_price_history(&date, flight_id, &seat_id, price);
+        let reservation = &self.reservations[&reservation_id];
+        self.hooks.iter().for_each(|h| h.on_reservation_created(reservation));
+        reservation_id
+    }
+
+    fn record_price_history(&mut self, date: &str, flight_id: u32, seat_id: &str, price: u32) {
+        let flight = self.flights.get(&flight_id).unwrap();
+        let (class_index, _) = match flight.get_seat_class(seat_id) {
+            Some(seat_class) => seat_class,
+            None => return,
+        };
+        let class_index = class_index as usize;
+        let capacity = flight.class_capacity(class_index);
+        let available = self.available_seats_per_class(date, flight)[class_index - 1];
+        let occupancy_pct = (capacity - available) as f32 / capacity as f32 * 100.0;
+
+        self.price_history.push(PriceHistoryEntry {
+            timestamp: self.clock.format("%Y/%m/%d-%H:%M:%S").to_string(),
+            date: date.to_string(),
+            flight_id,
+            class_index,
+            price,
+            occupancy_pct,
+        });
+    }
+
+    fn refundable_amount(&self, reservation: &Reservation) -> u32 {
+        if self.fee_refundable {
+            reservation.total_price
+        } else {
+            reservation.base_price
+        }
+    }
+
+    fn apply_service_fee(&mut self, reservation_id: u32) -> u32 {
+        let service_fee = self.service_fee;
+        let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+        reservation.service_fee = service_fee;
+        reservation.total_price = reservation.base_price + service_fee;
+        reservation.total_price
+    }
+
+    fn issue_voucher(&mut self, user_id: &str, reservation_id: u32, amount: u32) -> u32 {
+        let voucher_id = self.next_voucher_id;
+        self.vouchers.insert(
+            voucher_id,
+            RefundVoucher {
+                voucher_id,
+                user_id: user_id.to_string(),
+                reservation_id,
+                amount,
+            },
+        );
+        self.next_voucher_id += 1;
+        voucher_id
+    }
+
+    fn itinerary_of(&self, reservation_id: u32) -> Option<u32> {
+        self.itineraries
+            .values()
+            .find(|itinerary| itinerary.reservation_ids.contains(&reservation_id))
+            .map(|itinerary| itinerary.itinerary_id)
+    }
+
+    fn remove_itinerary_if_cancelled(&mut self, reservation_id: u32) {
+        let itinerary_id = match self.itinerary_of(reservation_id) {
+            Some(itinerary_id) => itinerary_id,
+            None => return,
+        };
+        let all_cancelled = self.itineraries[&itinerary_id]
+            .reservation_ids
+            .iter()
+            .all(|id| self.reservations.get(id).is_none_or(|r| r.is_cancelled));
+        if all_cancelled {
+            self.itineraries.remove(&itinerary_id);
+        }
+    }
+
+    fn process_reserve(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+    ) -> String {
+        self.process_reserve_with_options(
+            current_datetime,
+            user_id,
+            date,
+            flight_id,
+            seat_id,
+            &ReserveOptions::default(),
+        )
+    }
+
+    fn process_reserve_with_options(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+        options: &ReserveOptions,
+    ) -> String {
+        let current_dt = NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S").ok();
+        if let Some(current_dt) = current_dt {
+            if self.rate_limit_exceeded(user_id, current_dt) {
+                return self.msg("reserve.rate_limit_exceeded");
+            }
+        }
+        if let (Some(key), Some(current_dt)) = (&options.idem_key, current_dt) {
+            if let Some(&(reservation_id, added_at)) = self.idempotency_keys.get(key) {
+                match self.reservations.get(&reservation_id) {
+                    _ if current_dt - added_at >= Duration::hours(IDEMPOTENCY_KEY_HOURS) => {}
+                    Some(reservation) if reservation.user_id == user_id => {
+                        return self.reserve_response(user_id, reservation_id)
+                    }
+                    _ => return self.msg("reserve.idempotency_key_conflict"),
+                }
+            }
+        }
+
+        let reservation_id = match self.reserve_transaction(
+            current_datetime,
+            user_id,
+            date,
+            flight_id,
+            seat_id,
+            options,
+        ) {
+            Ok(reservation_id) => reservation_id,
+            Err(ReservationError::Rejected(reason)) => {
+                return self.msg(&format!("reserve.{}", reason))
+            }
+            Err(ReservationError::Inconsistent) => return self.msg("reserve.internal_error"),
+        };
+        if let (Some(key), Some(current_dt)) = (&options.idem_key, current_dt) {
+            self.idempotency_keys.insert(key.clone(), (reservation_id, current_dt));
+        }
+        let mut result = self.reserve_response(user_id, reservation_id);
+        if let Some(alert) = self.check_capacity_alerts(date, flight_id, seat_id) {
+            result.push('\n');
+            result.push_str(&alert);
+        }
+        result
+    }
+
+    fn check_capacity_alerts(
+        &mut self,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+    ) -> Option<String> {
+        let flight = &self.flights[&flight_id];
+        let class_index = flight.get_seat_class(seat_id)?.0 as usize;
+        let capacity = flight.class_capacity(class_index);
+        let available = self.available_seats_per_class(date, flight)[class_index - 1];
+        let occupancy_pct = (capacity - available) * 100 / capacity;
+
+        let mut fired = false;
+        for alert in &mut self.capacity_alerts {
+            if !alert.triggered
+                && alert.flight_id == flight_id
+                && alert.class_index == class_index
+                && occupancy_pct >= alert.threshold_pct
+            {
+                alert.triggered = true;
+                fired = true;
+            }
+        }
+        fired.then(|| {
+            self.msg_fmt("notice.capacity_alert", &[&flight_id, &class_index, &occupancy_pct])
+        })
+    }
+
+    fn process_add_capacity_alert(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        class_index: usize,
+        threshold_pct: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("add-capacity-alert.unauthorized_operation");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("add-capacity-alert.flight_not_found"),
+        };
+        if class_index == 0 || class_index > flight.seat_classes.len() {
+            return self.msg("add-capacity-alert.invalid_class");
+        }
+        if threshold_pct == 0 || threshold_pct > 100 {
+            return self.msg("add-capacity-alert.invalid_percent");
+        }
+
+        let alert_id = self.capacity_alerts.len() as u32 + 1;
+        self.capacity_alerts.push(CapacityAlert {
+            alert_id,
+            flight_id,
+            class_index,
+            threshold_pct,
+            triggered: false,
+        });
+        self.record_audit(
+            "admin",
+            "add-capacity-alert",
+            format!(
+                "alert_id={} flight_id={} class={} threshold={}",
+                alert_id, flight_id, class_index, threshold_pct
+            ),
+        );
+        self.msg_fmt("add-capacity-alert.success", &[&alert_id])
+    }
+
+    fn process_list_capacity_alerts(&self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("list-capacity-alerts.unauthorized_operation");
+        }
+        let mut result = vec![format!("list-capacity-alerts: {}", self.capacity_alerts.len())];
+        for alert in &self.capacity_alerts {
+            result.push(format!(
+                "alert {}: flight {} class {} threshold {}% {}",
+                alert.alert_id,
+                alert.flight_id,
+                alert.class_index,
+                alert.threshold_pct,
+                if alert.triggered { "triggered" } else { "pending" }
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn reserve_response(&self, user_id: &str, reservation_id: u32) -> String {
+        let reservation = &self.reservations[&reservation_id];
+        let mut result = format!("reserve: {} {}", reservation_id, reservation.total_price);
+        if reservation.service_fee > 0 {
+            result.push_str(&format!(
+                " (base={} fee={})",
+                reservation.base_price, reservation.service_fee
+            ));
+        }
+        if self.alert_prefs.get(user_id).is_some_and(|prefs| prefs.confirmation) {
+            result.push('\n');
+            result.push_str(
+                &self.msg_fmt("notice.confirmation_sent", &[&user_id, &reservation_id]),
+            );
+        }
+        result
+    }
+
+    fn process_purge_idempotency_keys(&mut self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("purge-idempotency-keys.invalid_datetime"),
+        };
+
+        let before = self.idempotency_keys.len();
+        self.idempotency_keys.retain(|_, (_, added_at)| {
+            current_dt - *added_at < Duration::hours(IDEMPOTENCY_KEY_HOURS)
+        });
+        let removed = before - self.idempotency_keys.len();
+        self.msg_fmt("purge-idempotency-keys.success", &[&removed])
+    }
+
+    fn reserve_transaction(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+        options: &ReserveOptions,
+    ) -> Result<u32, ReservationError> {
+        if self.blocked_users.contains(user_id) {
+            return Err(ReservationError::Rejected("user_blocked"));
+        }
+
+        if !self.flights.contains_key(&flight_id) {
+            return Err(ReservationError::Rejected("flight_not_found"));
+        }
+
+        let flight = self.flights.get(&flight_id).unwrap();
+        if !flight.operates_on(date) {
+            return Err(ReservationError::Rejected("flight_not_operating_on_date"));
+        }
+        if let Err(details) = flight.verify_seat_class_order() {
+            self.record_audit("system", "seat-class-integrity-error", details);
+            return Err(ReservationError::Rejected("flight_data_integrity_error"));
+        }
+
+        let current_dt = NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S")
+            .map_err(|_| ReservationError::Rejected("invalid_datetime"))?;
+
+        let flight_dt = match self.get_flight_datetime(date, flight) {
+            Some(dt) => dt,
+            None => return Err(ReservationError::Rejected("invalid_flight_datetime")),
+        };
+
+        if self.is_too_late(current_dt, flight_dt) {
+            return Err(ReservationError::Rejected("too_late"));
+        }
+
+        if self.is_seat_reserved(date, flight_id, seat_id) {
+            return Err(ReservationError::Rejected("already_reserved"));
+        }
+
+        if self
+            .active_auctions
+            .contains_key(&self.seat_key(date, flight_id, seat_id))
+        {
+            return Err(ReservationError::Rejected("seat_under_auction"));
+        }
+
+        let (_, mut price) = match flight.get_seat_class(seat_id) {
+            Some((sc, pr)) => (sc, pr),
+            None => return Err(ReservationError::Rejected("invalid_seat_id")),
+        };
+        if !flight.seat_type_allowed(seat_id) {
+            return Err(ReservationError::Rejected("seat_type_not_allowed_in_this_class"));
+        }
+        if flight.violates_seat_policy(seat_id, options.passenger_age, options.needs_assistance) {
+            return Err(ReservationError::Rejected("seat_policy_violation"));
+        }
+        if self.daily_reservation_limit_reached(user_id, current_dt.date()) {
+            return Err(ReservationError::Rejected("daily_reservation_limit_reached"));
+        }
+
+        let list_price = price;
+        if let Some(code) = &options.promo_code {
+            let promo = match self.promo_codes.get(code) {
+                Some(promo) => promo,
+                None => return Err(ReservationError::Rejected("invalid_promo_code")),
+            };
+            if current_dt.date() > promo.expiry_date {
+                return Err(ReservationError::Rejected("promo_code_expired"));
+            }
+            if promo.uses >= promo.max_uses {
+                return Err(ReservationError::Rejected("promo_code_exhausted"));
+            }
+            price = promo.discount.apply(price);
+        }
+        let discount_type = if options.promo_code.is_some() {
+            Some(DiscountType::PromoCode)
+        } else if self.first_flyer_discount_pct > 0 && self.is_first_reservation(user_id) {
+            price = PromoDiscount::Percent(self.first_flyer_discount_pct).apply(price);
+            Some(DiscountType::FirstFlyer)
+        } else {
+            None
+        };
+        let fare_discount = list_price - price;
+        price += flight.fuel_surcharge;
+
+        let commission_rate = match &options.agent_id {
+            Some(agent_id) => match self.commission_rates.get(agent_id) {
+                Some(&rate) => rate,
+                None => return Err(ReservationError::Rejected("unknown_agent")),
+            },
+            None => 0,
+        };
+
+        let mut reservation = self.new_reservation(user_id, date, flight_id, seat_id, price);
+        reservation.service_fee = self.service_fee;
+        reservation.total_price = reservation.base_price + reservation.service_fee;
+        reservation.promo_code = options.promo_code.clone();
+        if let Some(agent_id) = &options.agent_id {
+            reservation.agent_id = Some(agent_id.clone());
+            reservation.commission = price * commission_rate / 100;
+        }
+        reservation.passenger_age = options.passenger_age;
+        reservation.needs_assistance = options.needs_assistance;
+        reservation.discount_type = discount_type;
+        reservation.fare_discount = fare_discount;
+        reservation.booked_on = Some(current_dt.date());
+
+        let checkpoint = self.snapshot(&reservation, options.promo_code.as_deref());
+        let applied = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            if let Some(code) = &options.promo_code {
+                self.promo_codes.get_mut(code).unwrap().uses += 1;
+            }
+            self.insert_reservation(reservation)
+        }));
+        let reservation_id = match applied {
+            Ok(reservation_id) => reservation_id,
+            Err(_) => {
+                self.restore(checkpoint);
+                return Err(ReservationError::Inconsistent);
+            }
+        };
+        self.record_audit(user_id, "reserve", format!("reservation_id={}", reservation_id));
+        Ok(reservation_id)
+    }
+
+    fn snapshot(&self, reservation: &Reservation, promo_code: Option<&str>) -> Checkpoint {
+        let seat_state = self
+            .seat_reservations
+            .get(&reservation.date)
+            .and_then(|flights_on_date| flights_on_date.get(&reservation.flight_id))
+            .and_then(|seats| seats.get(&reservation.seat_id))
+            .copied();
+        Checkpoint {
+            reservation_id: reservation.reservation_id,
+            next_reservation_id: self.next_reservation_id,
+            seat: (reservation.date.clone(), reservation.flight_id, reservation.seat_id.clone()),
+            seat_state,
+            promo_uses: promo_code.and_then(|code| {
+                self.promo_codes.get(code).map(|promo| (code.to_string(), promo.uses))
+            }),
+            price_history_len: self.price_history.len(),
+            event_count: self.event_store.as_ref().map(|store| store.events.len()),
+        }
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        self.reservations.remove(&checkpoint.reservation_id);
+        self.next_reservation_id = checkpoint.next_reservation_id;
+        let (date, flight_id, seat_id) = checkpoint.seat;
+        if let Some(seats) = self
+            .seat_reservations
+            .get_mut(&date)
+            .and_then(|flights_on_date| flights_on_date.get_mut(&flight_id))
+        {
+            let previous = match checkpoint.seat_state {
+                Some(state) => seats.insert(seat_id.clone(), state),
+                None => seats.remove(&seat_id),
+            };
+            if previous == Some(SeatState::Reserved)
+                && checkpoint.seat_state != Some(SeatState::Reserved)
+            {
+                self.publish_seat_event(SeatEventType::Freed, &date, flight_id, &seat_id);
+            }
+        }
+        self.invalidate_search_cache(&date, flight_id);
+        if let Some((code, uses)) = checkpoint.promo_uses {
+            if let Some(promo) = self.promo_codes.get_mut(&code) {
+                promo.uses = uses;
+            }
+        }
+        self.price_history.truncate(checkpoint.price_history_len);
+        if let (Some(store), Some(event_count)) = (&mut self.event_store, checkpoint.event_count) {
+            store.events.truncate(event_count);
+        }
+    }
+
+    fn is_first_reservation(&self, user_id: &str) -> bool {
+        !self
+            .reservations
+            .values()
+            .any(|reservation| {
+                reservation.user_id == user_id
+                    && (!reservation.is_cancelled
+                        || reservation.discount_type == Some(DiscountType::FirstFlyer))
+            })
+    }
+
+    fn process_bulk_reserve(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_ids_csv: &str,
+    ) -> String {
+        if self.blocked_users.contains(user_id) {
+            return self.msg("bulk-reserve.user_blocked");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("bulk-reserve.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("bulk-reserve.flight_not_operating_on_date");
+        }
+        if let Err(details) = flight.verify_seat_class_order() {
+            self.record_audit("system", "seat-class-integrity-error", details);
+            return self.msg("bulk-reserve.flight_data_integrity_error");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("bulk-reserve.invalid_datetime"),
+        };
+        let flight_dt = match self.get_flight_datetime(date, flight) {
+            Some(dt) => dt,
+            None => return self.msg("bulk-reserve.invalid_flight_datetime"),
+        };
+        if self.is_too_late(current_dt, flight_dt) {
+            return self.msg("bulk-reserve.too_late");
+        }
+
+        let mut seats: Vec<(String, u32)> = vec![];
+        let mut seat_classes = vec![];
+        for seat_id in seat_ids_csv.split(',').map(str::trim) {
+            if seat_id.is_empty() || seats.iter().any(|(seen, _)| seen == seat_id) {
+                return self.msg("bulk-reserve.invalid_seat_ids");
+            }
+            let price = match flight.get_seat_class(seat_id) {
+                Some((class_index, price)) if flight.seat_type_allowed(seat_id) => {
+                    seat_classes.push(class_index as usize);
+                    price + flight.fuel_surcharge
+                }
+                _ => return self.msg("bulk-reserve.invalid_seat_ids"),
+            };
+            if self
+                .active_auctions
+                .contains_key(&self.seat_key(date, flight_id, seat_id))
+            {
+                return self.msg("bulk-reserve.seat_under_auction");
+            }
+            seats.push((seat_id.to_string(), price));
+        }
+
+        let lock_id = self.next_lock_id;
+        self.next_lock_id += 1;
+        for (i, (seat_id, _)) in seats.iter().enumerate() {
+            if self.seat_state(date, flight_id, seat_id) != SeatState::Free {
+                for (locked, _) in &seats[..i] {
+                    self.set_seat_state(date, flight_id, locked, SeatState::Free);
+                }
+                return self.msg_fmt("bulk-reserve.already_reserved", &[seat_id]);
+            }
+            self.set_seat_state(date, flight_id, seat_id, SeatState::TentativeLock(lock_id));
+        }
+
+        let discounts = self.group_discounts(flight_id, &seats, &seat_classes);
+        let group_discount: u32 = discounts.iter().sum();
+        let mut result = vec![self.msg_fmt("bulk-reserve.success", &[&seats.len()])];
+        for (i, (seat_id, price)) in seats.iter().enumerate() {
+            debug_assert_eq!(
+                self.seat_state(date, flight_id, seat_id),
+                SeatState::TentativeLock(lock_id)
+            );
+            let price = price - discounts[i];
+            let reservation_id = self.create_reservation(user_id, date, flight_id, seat_id, price);
+            self.reservations.get_mut(&reservation_id).unwrap().group_discount = discounts[i];
+            let total_price = match i {
+                0 => self.apply_service_fee(reservation_id),
+                _ => price,
+            };
+            result.push(format!("{} {} {}", reservation_id, seat_id, total_price));
+        }
+        if group_discount > 0 {
+            result.push(self.msg_fmt("bulk-reserve.group_discount_applied", &[&group_discount]));
+        }
+        result.join("\n")
+    }
+
+    fn group_discounts(
+        &self,
+        flight_id: u32,
+        seats: &[(String, u32)],
+        seat_classes: &[usize],
+    ) -> Vec<u32> {
+        let mut discounts = vec![0; seats.len()];
+        let mut priced = vec![false; seats.len()];
+        for rule in &self.group_rules {
+            if rule.flight_id.is_some_and(|rule_flight| rule_flight != flight_id) {
+                continue;
+            }
+            let mut applicable: Vec<usize> = (0..seats.len())
+                .filter(|&i| {
+                    !priced[i] && rule.class_index.is_none_or(|class| class == seat_classes[i])
+                })
+                .collect();
+            let group_size = (rule.buy_n + rule.free_m) as usize;
+            let free_count = applicable.len() / group_size * rule.free_m as usize;
+            for &i in &applicable {
+                priced[i] = true;
+            }
+            applicable.sort_by_key(|&i| seats[i].1);
+            for &i in applicable.iter().take(free_count) {
+                discounts[i] = seats[i].1;
+            }
+        }
+        discounts
+    }
+
+    fn process_add_group_rule(
+        &mut self,
+        admin_token: &str,
+        flight_id: Option<u32>,
+        class_index: Option<usize>,
+        buy_n: u32,
+        free_m: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("add-group-rule.unauthorized_operation");
+        }
+        if let Some(flight_id) = flight_id {
+            let flight = match self.flights.get(&flight_id) {
+                Some(flight) => flight,
+                None => return self.msg("add-group-rule.flight_not_found"),
+            };
+            if class_index.is_some_and(|class| class > flight.seat_classes.len()) {
+                return self.msg("add-group-rule.invalid_class");
+            }
+        }
+        if class_index == Some(0) {
+            return self.msg("add-group-rule.invalid_class");
+        }
+        if buy_n == 0 || free_m == 0 {
+            return self.msg("add-group-rule.invalid_rule");
+        }
+
+        let rule_id = self.group_rules.last().map_or(1, |rule| rule.rule_id + 1);
+        self.group_rules.push(GroupPricingRule {
+            rule_id,
+            flight_id,
+            class_index,
+            buy_n,
+            free_m,
+        });
+        let scope = |value: Option<String>| value.unwrap_or_else(|| "any".to_string());
+        let flight_scope = scope(flight_id.map(|id| id.to_string()));
+        let class_scope = scope(class_index.map(|class| class.to_string()));
+        self.record_audit(
+            "admin",
+            "add-group-rule",
+            format!(
+                "rule_id={} flight={} class={} buy={} free={}",
+                rule_id, flight_scope, class_scope, buy_n, free_m
+            ),
+        );
+        self.msg_fmt(
+            "add-group-rule.success",
+            &[&rule_id, &buy_n, &free_m, &flight_scope, &class_scope],
+        )
+    }
+
+    fn check_cancellable(
+        &self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+    ) -> Result<(&Reservation, NaiveDateTime, NaiveDateTime), &'static str> {
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return Err("reservation_not_found"),
+        };
+        if reservation.user_id != user_id {
+            return Err("unauthorized_operation");
+        }
+
+        let flight = self.flights.get(&reservation.flight_id).unwrap();
+        let current_dt = NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S")
+            .map_err(|_| "invalid_datetime")?;
+        let flight_dt = self
+            .get_flight_datetime(&reservation.date, flight)
+            .ok_or("invalid_flight_datetime")?;
+        if self.is_too_late(current_dt, flight_dt) {
+            return Err("too_late");
+        }
+        Ok((reservation, current_dt, flight_dt))
+    }
+
+    fn process_refund_estimate(
+        &self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+    ) -> String {
+        let (reservation, current_dt, flight_dt) =
+            match self.check_cancellable(current_datetime, user_id, reservation_id) {
+                Ok(checked) => checked,
+                Err(reason) => return self.msg(&format!("refund-estimate.{}", reason)),
+            };
+
+        let refund = self
+            .insurance_policies
+            .values()
+            .find(|policy| {
+                policy.reservation_id == reservation_id
+                    && !policy.is_claimed
+                    && policy.coverage_type != CoverageType::DelayCompensation
+            })
+            .map_or(0, |policy| {
+                policy.coverage_type.coverage_amount(self.refundable_amount(reservation))
+            });
+        let fee = reservation.total_price - refund;
+        let cutoff = self.is_too_late(current_dt + Duration::hours(24), flight_dt);
+
+        self.msg_fmt("refund-estimate.eligible", &[&refund, &fee, &cutoff])
+    }
+
+    fn process_cancel(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+    ) -> String {
+        let current_dt = match self.check_cancellable(current_datetime, user_id, reservation_id) {
+            Ok((_, current_dt, _)) => current_dt,
+            Err(reason) => return self.msg(&format!("cancel.{}", reason)),
+        };
+
+        self.record_event(|| SystemEvent::ReservationCancelled { reservation_id });
+        let reservation_mut = self.reservations.get_mut(&reservation_id).unwrap();
+        reservation_mut.is_cancelled = true;
+
+        let date = reservation_mut.date.clone();
+        let flight_id = reservation_mut.flight_id;
+        let seat_id = reservation_mut.seat_id.clone();
+
+        self.unreserve_seat(&date, flight_id, &seat_id);
+        self.remove_itinerary_if_cancelled(reservation_id);
+        self.notify_reservation_cancelled(reservation_id);
+        for linked_id in self.linked_reservation_ids(reservation_id) {
+            self.record_event(|| SystemEvent::ReservationCancelled {
+                reservation_id: linked_id,
+            });
+            self.reservations.get_mut(&linked_id).unwrap().is_cancelled = true;
+            self.remove_itinerary_if_cancelled(linked_id);
+            self.notify_reservation_cancelled(linked_id);
+        }
+
+        let mut result = self.msg("cancel.success");
+        if let Some(notice) = self.promote_waitlist(current_dt, &date, flight_id, &seat_id) {
+            result.push('\n');
+            result.push_str(&notice);
+        }
+        if self.alert_prefs.get(user_id).is_some_and(|prefs| prefs.cancellation_notice) {
+            result.push('\n');
+            result.push_str(
+                &self.msg_fmt("notice.cancellation_sent", &[&user_id, &reservation_id]),
+            );
+        }
+        result
+    }
+
+    fn linked_reservation_ids(&self, reservation_id: u32) -> Vec<u32> {
+        let parent_id = self.reservations[&reservation_id].parent_reservation_id;
+        let mut linked: Vec<u32> = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && (Some(reservation.reservation_id) == parent_id
+                        || reservation.parent_reservation_id == Some(reservation_id))
+            })
+            .map(|reservation| reservation.reservation_id)
+            .collect();
+        linked.sort();
+        linked
+    }
+
+    fn process_split_reservation(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+        child_passenger_name: &str,
+        child_age: u8,
+    ) -> String {
+        let reservation = match self.check_cancellable(current_datetime, user_id, reservation_id) {
+            Ok((reservation, _, _)) => reservation,
+            Err(reason) => return self.msg(&format!("split-reservation.{}", reason)),
+        };
+        if reservation.passenger_age.is_some_and(|age| age < MIN_ADULT_AGE) {
+            return self.msg("split-reservation.passenger_not_adult");
+        }
+        if reservation.parent_reservation_id.is_some()
+            || !self.linked_reservation_ids(reservation_id).is_empty()
+        {
+            return self.msg("split-reservation.already_split");
+        }
+        if child_age as u32 >= MIN_ADULT_AGE {
+            return self.msg("split-reservation.invalid_child_age");
+        }
+        if child_passenger_name.is_empty()
+            || child_passenger_name.chars().count() > MAX_PASSENGER_NAME_CHARS
+        {
+            return self.msg("split-reservation.invalid_name");
+        }
+
+        let child_price = reservation.base_price / 2;
+        let mut child = Reservation::new(
+            0,
+            user_id.to_string(),
+            reservation.date.clone(),
+            reservation.flight_id,
+            reservation.seat_id.clone(),
+            child_price,
+        );
+        child.passenger_name = child_passenger_name.to_string();
+        child.passenger_age = Some(child_age as u32);
+        child.parent_reservation_id = Some(reservation_id);
+        child.booked_on = reservation.booked_on;
+
+        while self.reservations.contains_key(&self.next_reservation_id) {
+            self.next_reservation_id += 1;
+        }
+        let child_id = self.next_reservation_id;
+        self.next_reservation_id += 1;
+        child.reservation_id = child_id;
+
+        let adult = self.reservations.get_mut(&reservation_id).unwrap();
+        adult.base_price -= child_price;
+        adult.total_price = adult.base_price + adult.service_fee;
+        self.record_event(|| SystemEvent::ReservationCreated {
+            reservation_id: child_id,
+            user_id: child.user_id.clone(),
+            date: child.date.clone(),
+            flight_id: child.flight_id,
+            seat_id: child.seat_id.clone(),
+            price: child_price,
+        });
+        self.hooks.iter().for_each(|h| h.on_reservation_created(&child));
+        self.reservations.insert(child_id, child);
+        self.record_audit(
+            user_id,
+            "split-reservation",
+            format!("reservation_id={} child_reservation_id={}", reservation_id, child_id),
+        );
+        self.msg_fmt("split-reservation.success", &[&reservation_id, &child_id])
+    }
+
+    fn promote_waitlist(
+        &mut self,
+        current_dt: NaiveDateTime,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+    ) -> Option<String> {
+        let flight = self.flights.get(&flight_id).unwrap();
+        let (class_index, price) = flight.get_seat_class(seat_id)?;
+        let price = price + flight.fuel_surcharge;
+        let key = (date.to_string(), flight_id, class_index);
+        let position = self
+            .waitlists
+            .get(&key)?
+            .iter()
+            .take(self.waitlist_max_retry as usize)
+            .position(|entry| {
+                entry.expires_at > current_dt && !self.blocked_users.contains(&entry.user_id)
+            })?;
+
+        let entry = self.waitlists.get_mut(&key).unwrap().remove(position);
+        let reservation_id =
+            self.create_reservation(&entry.user_id, date, flight_id, seat_id, price);
+        Some(self.msg_fmt("notice.waitlist_promoted", &[&entry.user_id, &reservation_id]))
+    }
+
+    fn process_export_events(&self) -> String {
+        let store = match &self.event_store {
+            Some(store) => store,
+            None => return self.msg("export-events.event_sourcing_disabled"),
+        };
+        let mut result = vec![format!("export-events: {}", store.events.len())];
+        result.extend(store.events.iter().map(|event| event.to_json().to_string()));
+        result.join("\n")
+    }
+
+    fn process_rebuild_from_events(&self, event_count: usize) -> String {
+        let store = match &self.event_store {
+            Some(store) => store,
+            None => return self.msg("rebuild-from-events.event_sourcing_disabled"),
+        };
+        if event_count > store.events.len() {
+            return self.msg("rebuild-from-events.invalid_event_index");
+        }
+        let system = ReservationSystem::rebuild_from_events(&store.events[..event_count]);
+        let active = system
+            .reservations
+            .values()
+            .filter(|reservation| !reservation.is_cancelled)
+            .count();
+        self.msg_fmt(
+            "rebuild-from-events.success",
+            &[&event_count, &system.flights.len(), &system.reservations.len(), &active],
+        )
+    }
+
+    fn process_join_waitlist(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        class_index: u32,
+    ) -> String {
+        if self.blocked_users.contains(user_id) {
+            return self.msg("join-waitlist.user_blocked");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("join-waitlist.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("join-waitlist.flight_not_operating_on_date");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("join-waitlist.invalid_datetime"),
+        };
+        let flight_dt = match self.get_flight_datetime(date, flight) {
+            Some(dt) => dt,
+            None => return self.msg("join-waitlist.invalid_flight_datetime"),
+        };
+        if self.is_too_late(current_dt, flight_dt) {
+            return self.msg("join-waitlist.too_late");
+        }
+        if class_index == 0 || class_index as usize > flight.seat_classes.len() {
+            return self.msg("join-waitlist.invalid_class");
+        }
+        if self.available_seats_per_class(date, flight)[class_index as usize - 1] > 0 {
+            return self.msg("join-waitlist.seats_available");
+        }
+
+        let queue = self
+            .waitlists
+            .entry((date.to_string(), flight_id, class_index))
+            .or_default();
+        if queue.iter().any(|entry| entry.user_id == user_id) {
+            return self.msg("join-waitlist.already_waitlisted");
+        }
+        queue.push(WaitlistEntry {
+            user_id: user_id.to_string(),
+            expires_at: current_dt + Duration::hours(WAITLIST_WINDOW_HOURS),
+        });
+        let position = queue.len();
+
+        self.msg_fmt("join-waitlist.success", &[&position])
+    }
+
+    fn process_prune_waitlist(&mut self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("prune-waitlist.invalid_datetime"),
+        };
+
+        let mut removed = 0;
+        for queue in self.waitlists.values_mut() {
+            let before = queue.len();
+            queue.retain(|entry| entry.expires_at > current_dt);
+            removed += before - queue.len();
+        }
+        self.waitlists.retain(|_, queue| !queue.is_empty());
+
+        self.msg_fmt("prune-waitlist.success", &[&removed])
+    }
+
+    fn process_register_task(
+        &mut self,
+        admin_token: &str,
+        task_type: TaskType,
+        interval_minutes: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("register-task.unauthorized_operation");
+        }
+        if interval_minutes == 0 {
+            return self.msg("register-task.invalid_interval");
+        }
+
+        let task_id = self.scheduled_tasks.len() as u32 + 1;
+        self.scheduled_tasks.push(ScheduledTask {
+            task_id,
+            trigger_every_minutes: interval_minutes,
+            last_run: None,
+            task_type,
+        });
+        self.record_audit(
+            "admin",
+            "register-task",
+            format!("task_id={} type={} every={}", task_id, task_type, interval_minutes),
+        );
+        self.msg_fmt("register-task.success", &[&task_id, &task_type, &interval_minutes])
+    }
+
+    fn process_run_due_tasks(&mut self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("run-due-tasks.invalid_datetime"),
+        };
+
+        let due: Vec<(usize, TaskType)> = self
+            .scheduled_tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| {
+                task.last_run.is_none_or(|last_run| {
+                    current_dt - last_run
+                        >= Duration::minutes(task.trigger_every_minutes as i64)
+                })
+            })
+            .map(|(i, task)| (i, task.task_type))
+            .collect();
+
+        let mut result = vec![self.msg_fmt("run-due-tasks.summary", &[&due.len()])];
+        for (i, task_type) in due {
+            let output = match task_type {
+                TaskType::PurgeLocks => self.process_purge_expired_locks(current_datetime),
+                TaskType::AutoCancelNoCheckin => {
+                    self.process_auto_cancel_no_checkin(current_datetime)
+                }
+                TaskType::PruneWaitlist => self.process_prune_waitlist(current_datetime),
+            };
+            self.scheduled_tasks[i].last_run = Some(current_dt);
+            let output = output.split_once(": ").map_or(output.as_str(), |(_, rest)| rest);
+            result.push(format!(
+                "task {} {}: {}",
+                self.scheduled_tasks[i].task_id, task_type, output
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn rate_limit_exceeded(&mut self, user_id: &str, current_dt: NaiveDateTime) -> bool {
+        let config = match self.rate_limit_config {
+            Some(config) => config,
+            None => return false,
+        };
+        let window = Duration::seconds(config.window_seconds as i64);
+        let requests = self.rate_limit_counters.entry(user_id.to_string()).or_default();
+        while requests.front().is_some_and(|&requested_at| current_dt - requested_at >= window) {
+            requests.pop_front();
+        }
+        if requests.len() >= config.max_requests as usize {
+            return true;
+        }
+        requests.push_back(current_dt);
+        false
+    }
+
+    fn process_set_rate_limit(
+        &mut self,
+        admin_token: &str,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-rate-limit.unauthorized_operation");
+        }
+        if max_requests == 0 || window_seconds == 0 {
+            return self.msg("set-rate-limit.invalid_limit");
+        }
+        self.rate_limit_config = Some(RateLimitConfig {
+            max_requests,
+            window_seconds,
+        });
+        self.rate_limit_counters.clear();
+        self.record_audit(
+            "admin",
+            "set-rate-limit",
+            format!("max_requests={} window_seconds={}", max_requests, window_seconds),
+        );
+        self.msg_fmt("set-rate-limit.success", &[&max_requests, &window_seconds])
+    }
+
+    fn process_clear_rate_limit(&mut self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-rate-limit.unauthorized_operation");
+        }
+        self.rate_limit_config = None;
+        self.rate_limit_counters.clear();
+        self.record_audit("admin", "set-rate-limit", "none".to_string());
+        self.msg("set-rate-limit.cleared")
+    }
+
+    fn daily_reservation_limit_reached(&self, user_id: &str, booking_date: NaiveDate) -> bool {
+        let limit = match self.max_reservations_per_user_per_day {
+            Some(limit) => limit,
+            None => return false,
+        };
+        if self
+            .user_profiles
+            .get(user_id)
+            .is_some_and(|profile| profile.corporate_account_id.is_some())
+        {
+            return false;
+        }
+        let booked = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.user_id == user_id
+                    && reservation.booked_on == Some(booking_date)
+            })
+            .count();
+        booked >= limit as usize
+    }
+
+    fn process_set_daily_reservation_limit(&mut self, admin_token: &str, limit: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-daily-reservation-limit.unauthorized_operation");
+        }
+        let limit = match limit {
+            "none" => None,
+            limit => match limit.parse::<u32>() {
+                Ok(limit) if limit > 0 => Some(limit),
+                _ => return self.msg("set-daily-reservation-limit.invalid_limit"),
+            },
+        };
+        self.max_reservations_per_user_per_day = limit;
+        let shown = limit.map_or("none".to_string(), |limit| limit.to_string());
+        self.record_audit("admin", "set-daily-reservation-limit", format!("limit={}", shown));
+        self.msg_fmt("set-daily-reservation-limit.success", &[&shown])
+    }
+
+    fn process_set_waitlist_max_retry(&mut self, admin_token: &str, max_retry: u32) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-waitlist-max-retry.unauthorized_operation");
+        }
+        if max_retry == 0 {
+            return self.msg("set-waitlist-max-retry.invalid_limit");
+        }
+        self.waitlist_max_retry = max_retry;
+        self.msg_fmt("set-waitlist-max-retry.success", &[&max_retry])
+    }
+
+    fn process_seat_search(
+        &self,
+        _current_datetime: &str,
+        date: &str,
+        flight_id: u32,
+    ) -> String {
+        if !self.flights.contains_key(&flight_id) {
+            return self.msg("seat-search.flight_not_found");
+        }
+
+        let flight = self.flights.get(&flight_id).unwrap();
+        if !flight.operates_on(date) {
+            return self.msg("seat-search.flight_not_operating_on_date");
+        }
+
+        let is_peak = self
+            .parse_date(date)
+            .is_some_and(|date| self.peak_dates.contains(&date));
+        let mut result = vec![match is_peak {
+            true => "seat-search: (peak)".to_string(),
+            false => "seat-search:".to_string(),
+        }];
+        result.extend(self.seat_grid(date, flight));
+
+        result.join("\n")
+    }
+
+    fn seat_grid(&self, date: &str, flight: &Flight) -> Vec<String> {
+        let mut seats = vec![];
+
+        for row in 1..=SEAT_ROWS {
+            let mut row_seats = HashMap::new();
+            for seat_type in &SeatType::variants() {
+                let seat_id = format!("{}{}", row, seat_type.as_char());
+                let seat_display = if self.is_seat_reserved(date, flight.flight_id, &seat_id) {
+                    "X".to_string()
+                } else {
+                    let (seat_class, _) = flight.get_seat_class(&seat_id).unwrap();
+                    seat_class.to_string()
+                };
+                row_seats.insert(seat_type.clone(), seat_display);
+            }
+            seats.push(row_seats);
+        }
+
+        let mut grid = vec![];
+        for seat_type in &SeatType::variants() {
+            let mut row_display = String::new();
+            for row in &seats {
+                row_display.push_str(&row[seat_type]);
+            }
+            grid.push(row_display);
+        }
+        grid
+    }
+
+    fn process_export_seat_map(&self, date: &str, flight_id: u32, col_width: u8) -> String {
+        if col_width == 0 || col_width > 20 {
+            return self.msg("export-seat-map.invalid_col_width");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("export-seat-map.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("export-seat-map.flight_not_operating_on_date");
+        }
+
+        let width = col_width as usize;
+        let seat_types = SeatType::variants();
+        let aisle_after = seat_types.len() / 2;
+        let border = format!("+{}+", "-".repeat(width));
+        let aisle = "   ";
+
+        let mut result = vec![format!("export-seat-map: flight {} {}", flight_id, date)];
+        let rows = flight.seat_classes.last().map_or(0, |seat_class| seat_class.column);
+        for row in 1..=rows {
+            let mut top = String::new();
+            let mut info = String::new();
+            for (i, seat_type) in seat_types.iter().enumerate() {
+                if i == aisle_after {
+                    top.push_str(aisle);
+                    info.push_str(aisle);
+                }
+                let seat_id = format!("{}{}", row, seat_type.as_char());
+                let status = if self.is_seat_reserved(date, flight_id, &seat_id) {
+                    "X".to_string()
+                } else {
+                    let (seat_class, _) = flight.get_seat_class(&seat_id).unwrap();
+                    seat_class.to_string()
+                };
+                let label: String = format!("{} {}", seat_id, status).chars().take(width).collect();
+                top.push_str(&border);
+                info.push_str(&format!("|{:<width$}|", label, width = width));
+            }
+            result.push(top.clone());
+            result.push(info);
+            result.push(top);
+        }
+        result.join("\n")
+    }
+
+    fn process_get_reservations(
+        &self,
+        current_datetime: &str,
+        user_id: &str,
+        fields_csv: Option<&str>,
+    ) -> String {
+        let fields = match fields_csv {
+            None | Some("*") => None,
+            Some(fields_csv) => {
+                let mut fields = vec![];
+                for name in fields_csv.split(',') {
+                    match ReservationField::parse(name) {
+                        Some(field) => fields.push(field),
+                        None => return self.msg_fmt("get-reservations.unknown_field", &[&name]),
+                    }
+                }
+                Some(fields)
+            }
+        };
+        let mut valid_reservations = vec![];
+
+        for reservation in self.reservations.values() {
+            if reservation.user_id == user_id
+                && (!reservation.is_cancelled || reservation.is_timeout_cancelled)
+            {
+                let flight = self.flights.get(&reservation.flight_id).unwrap();
+                let flight_dt = self.get_flight_datetime(&reservation.date, flight).unwrap();
+                valid_reservations.push((flight_dt, reservation.reservation_id, reservation));
+            }
+        }
+
+        valid_reservations.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut result = vec![format!("get-reservations: {}", valid_reservations.len())];
+        let mut itinerary_lines: HashMap<u32, Vec<String>> = HashMap::new();
+
+        for (_, _, reservation) in valid_reservations {
+            let flight = self.flights.get(&reservation.flight_id).unwrap();
+            if let Some(fields) = &fields {
+                let line = fields
+                    .iter()
+                    .map(|field| field.render(reservation, flight))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match self.itinerary_of(reservation.reservation_id) {
+                    Some(itinerary_id) => {
+                        itinerary_lines.entry(itinerary_id).or_default().push(line)
+                    }
+                    None => result.push(line),
+                }
+                continue;
+            }
+            let mut line = format!(
+                "reservation id: {}, price: {}, seat: {} {} {}, route: {} ({}) -> {} ({})",
+                reservation.reservation_id,
+                reservation.total_price,
+                reservation.date,
+                reservation.flight_id,
+                reservation.seat_id,
+                flight.departure_airport,
+                flight.departure_time,
+                flight.arrival_airport,
+                flight.arrival_time
+            );
+            if reservation.is_timeout_cancelled {
+                line.push_str(&self.msg("get-reservations.timeout_cancelled"));
+            }
+            if reservation.pending_fare_adjustment != 0 {
+                line.push_str(&self.msg_fmt(
+                    "get-reservations.fare_adjustment",
+                    &[&format!("{:+}", reservation.pending_fare_adjustment)],
+                ));
+            }
+            if let Some(policy) = self.insurance_policies.values().find(|policy| {
+                policy.reservation_id == reservation.reservation_id
+            }) {
+                line.push_str(&self.msg_fmt(
+                    "get-reservations.insurance",
+                    &[&policy.policy_id, &policy.coverage_type, &policy.premium],
+                ));
+                if policy.is_claimed {
+                    line.push_str(&self.msg("get-reservations.insurance_claimed"));
+                }
+            }
+            match self.itinerary_of(reservation.reservation_id) {
+                Some(itinerary_id) => itinerary_lines.entry(itinerary_id).or_default().push(line),
+                None => result.push(line),
+            }
+        }
+
+        let mut itineraries: Vec<&Itinerary> = self
+            .itineraries
+            .values()
+            .filter(|itinerary| itinerary.user_id == user_id)
+            .collect();
+        itineraries.sort_by_key(|itinerary| itinerary.itinerary_id);
+        for itinerary in itineraries {
+            if let Some(lines) = itinerary_lines.remove(&itinerary.itinerary_id) {
+                result.push(format!("itinerary {}:", itinerary.itinerary_id));
+                result.extend(lines);
+            }
+        }
+
+        if let Some(notices) = self.auction_notices.get(user_id) {
+            result.extend(notices.iter().cloned());
+        }
+
+        let current_dt = NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S").ok();
+        let mut swap_requests: Vec<&SwapRequest> = self
+            .swap_requests
+            .values()
+            .filter(|request| {
+                request.target_user_id == user_id
+                    && request.status == SwapStatus::Pending
+                    && current_dt.is_none_or(|dt| dt < request.expires_at)
+            })
+            .collect();
+        swap_requests.sort_by_key(|request| request.request_id);
+        for request in swap_requests {
+            result.push(self.msg_fmt(
+                "get-reservations.swap_request",
+                &[
+                    &request.request_id,
+                    &request.initiator_user_id,
+                    &self.reservations[&request.initiator_reservation_id].seat_id,
+                    &self.reservations[&request.target_reservation_id].seat_id,
+                    &request.target_reservation_id,
+                ],
+            ));
+        }
+
+        result.join("\n")
+    }
+
+    fn process_calendar_view(&self, user_id: &str, year: u32, month: u32) -> String {
+        let first = match NaiveDate::from_ymd_opt(year as i32, month, 1) {
+            Some(date) => date,
+            None => return self.msg("calendar-view.invalid_month"),
+        };
+        let next_month = match month {
+            12 => NaiveDate::from_ymd_opt(year as i32 + 1, 1, 1),
+            _ => NaiveDate::from_ymd_opt(year as i32, month + 1, 1),
+        };
+        let days_in_month = match next_month {
+            Some(next_month) => (next_month - first).num_days() as u32,
+            None => return self.msg("calendar-view.invalid_month"),
+        };
+
+        let mut departures: HashMap<u32, Vec<u32>> = HashMap::new();
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled || reservation.user_id != user_id {
+                continue;
+            }
+            let date = match NaiveDate::parse_from_str(&reservation.date, "%Y/%m/%d") {
+                Ok(date) if date.year() == first.year() && date.month() == month => date,
+                _ => continue,
+            };
+            let flight_ids = departures.entry(date.day()).or_default();
+            if !flight_ids.contains(&reservation.flight_id) {
+                flight_ids.push(reservation.flight_id);
+            }
+        }
+
+        let cells: Vec<String> = (1..=days_in_month)
+            .map(|day| match departures.get_mut(&day) {
+                Some(flight_ids) => {
+                    flight_ids.sort();
+                    let ids: Vec<String> = flight_ids.iter().map(u32::to_string).collect();
+                    format!("{}:{}", day, ids.join(","))
+                }
+                None => day.to_string(),
+            })
+            .collect();
+        let width = cells.iter().map(String::len).max().unwrap_or(2).max(2);
+
+        let mut weeks = vec![];
+        let mut week = vec![String::new(); first.weekday().num_days_from_monday() as usize];
+        for cell in cells {
+            week.push(cell);
+            if week.len() == 7 {
+                weeks.push(std::mem::take(&mut week));
+            }
+        }
+        if !week.is_empty() {
+            week.resize(7, String::new());
+            weeks.push(week);
+        }
+
+        let render = |cells: &[String]| {
+            cells
+                .iter()
+                .map(|cell| format!("{:>width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join(" ")
+                .trim_end()
+                .to_string()
+        };
+        let header: Vec<String> = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+            .iter()
+            .map(|day| day.to_string())
+            .collect();
+
+        let mut result = vec![format!("calendar-view: {} {}/{:02}", user_id, year, month)];
+        result.push(render(&header));
+        result.extend(weeks.iter().map(|week| render(week)));
+        result.join("\n")
+    }
+
+    fn process_admin_get_reservations(
+        &mut self,
+        admin_token: &str,
+        page: u32,
+        page_size: u32,
+        filter_cancelled: bool,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("admin-reservations.unauthorized_operation");
+        }
+        if page_size == 0 {
+            return self.msg("admin-reservations.invalid_page_size");
+        }
+
+        let mut reservations: Vec<&Reservation> = self
+            .reservations
+            .values()
+            .filter(|reservation| !filter_cancelled || !reservation.is_cancelled)
+            .collect();
+        reservations.sort_by_key(|reservation| reservation.reservation_id);
+
+        let total = reservations.len();
+        let page_size = page_size as usize;
+        let total_pages = total.div_ceil(page_size).max(1);
+        if page == 0 || page as usize > total_pages {
+            return self.msg("admin-reservations.page_out_of_range");
+        }
+
+        let mut result = vec![format!("admin-reservations: total={}", total)];
+        for reservation in reservations
+            .iter()
+            .skip((page as usize - 1) * page_size)
+            .take(page_size)
+        {
+            let status = if reservation.is_timeout_cancelled {
+                "timeout-cancelled"
+            } else if reservation.is_cancelled {
+                "cancelled"
+            } else if reservation.is_checked_in {
+                "checked-in"
+            } else {
+                "active"
+            };
+            result.push(format!(
+                "{} {} {} {} {} {} {}",
+                reservation.reservation_id,
+                reservation.user_id,
+                reservation.date,
+                reservation.flight_id,
+                reservation.seat_id,
+                reservation.total_price,
+                status
+            ));
+        }
+        result.push(format!("page {}/{}", page, total_pages));
+
+        self.record_audit(
+            "admin",
+            "admin-reservations",
+            format!("page={} page_size={}", page, page_size),
+        );
+        result.join("\n")
+    }
+
+    fn process_flight_search(
+        &mut self,
+        current_datetime: &str,
+        date: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+        depart_after: Option<&str>,
+        depart_before: Option<&str>,
+    ) -> String {
+        let parse_bound = |bound: Option<&str>| match bound {
+            None | Some("*") => Ok(None),
+            Some(time) => NaiveTime::parse_from_str(time, "%H:%M:%S").map(Some),
+        };
+        let window = match (parse_bound(depart_after), parse_bound(depart_before)) {
+            (Ok(after), Ok(before)) => (after, before),
+            _ => return self.msg("flight-search.invalid_time"),
+        };
+        if window != (None, None) {
+            return self.flight_search_result(date, departure_airport, arrival_airport, window);
+        }
+
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) if self.cache_ttl_seconds > 0 => dt,
+            _ => return self.flight_search_result(date, departure_airport, arrival_airport, window),
+        };
+
+        let key = (
+            date.to_string(),
+            current_dt.date().format("%Y/%m/%d").to_string(),
+            departure_airport,
+            arrival_airport,
+        );
+        if let Some((result, cached_at)) = self.search_cache.entries.get(&key) {
+            let age = current_dt - *cached_at;
+            if age >= Duration::zero() && age < Duration::seconds(self.cache_ttl_seconds as i64) {
+                return result.clone();
+            }
+        }
+
+        let result = self.flight_search_result(date, departure_airport, arrival_airport, window);
+        self.search_cache.entries.insert(key, (result.clone(), current_dt));
+        result
+    }
+
+    fn flight_search_result(
+        &self,
+        date: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+        (depart_after, depart_before): (Option<NaiveTime>, Option<NaiveTime>),
+    ) -> String {
+        let matching_flights: Vec<&Flight> = self
+            .matching_flights(date, departure_airport, arrival_airport)
+            .into_iter()
+            .filter(|flight| {
+                let departure = NaiveTime::parse_from_str(&flight.departure_time, "%H:%M:%S");
+                let departure = match departure {
+                    Ok(departure) => departure,
+                    Err(_) => return depart_after.is_none() && depart_before.is_none(),
+                };
+                depart_after.is_none_or(|after| departure >= after)
+                    && depart_before.is_none_or(|before| departure <= before)
+            })
+            .collect();
+
+        let mut result = vec![format!("flight-search: {}", matching_flights.len())];
+
+        let delimiter = self.output_delimiter.to_string();
+        for flight in matching_flights {
+            if self.output_delimiter != ' ' {
+                result.push(
+                    [
+                        "flight_id",
+                        &flight.flight_id.to_string(),
+                        "dep",
+                        &flight.departure_time,
+                        "arr",
+                        &flight.arrival_time,
+                    ]
+                    .join(&delimiter),
+                );
+            } else {
+                result.push(format!(
+                    "{} {} {}",
+                    flight.flight_id, flight.departure_time, flight.arrival_time
+                ));
+            }
+
+            let available = self.available_seats_per_class(date, flight);
+            for (i, seat_class) in flight.seat_classes.iter().enumerate() {
+                let seats_count = available[i];
+                if self.output_delimiter != ' ' {
+                    let mut fields = vec![
+                        "class".to_string(),
+                        (i + 1).to_string(),
+                        "available".to_string(),
+                        seats_count.to_string(),
+                        "price".to_string(),
+                        seat_class.price.to_string(),
+                    ];
+                    if flight.fuel_surcharge != 0 {
+                        fields.extend([
+                            "surcharge".to_string(),
+                            flight.fuel_surcharge.to_string(),
+                            "total".to_string(),
+                            (seat_class.price + flight.fuel_surcharge).to_string(),
+                        ]);
+                    }
+                    result.push(fields.join(&delimiter));
+                } else if flight.fuel_surcharge == 0 {
+                    result.push(self.msg_fmt(
+                        "flight-search.class_availability",
+                        &[&(i + 1), &seats_count, &seat_class.price],
+                    ));
+                } else {
+                    result.push(self.msg_fmt(
+                        "flight-search.class_availability_surcharge",
+                        &[
+                            &(i + 1),
+                            &seats_count,
+                            &seat_class.price,
+                            &flight.fuel_surcharge,
+                            &(seat_class.price + flight.fuel_surcharge),
+                        ],
+                    ));
+                }
+            }
+            for announcement in &flight.announcements {
+                result.push(format!("  {}", announcement));
+            }
+        }
+
+        result.join("\n")
+    }
+
+    fn process_next_available_flight(
+        &self,
+        current_datetime: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+    ) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("next-available.invalid_datetime"),
+        };
+
+        for day in current_dt.date().iter_days().take(NEXT_AVAILABLE_SEARCH_DAYS) {
+            let date = day.format("%Y/%m/%d").to_string();
+            for flight in self.matching_flights(&date, departure_airport, arrival_airport) {
+                if self
+                    .get_flight_datetime(&date, flight)
+                    .is_none_or(|flight_dt| flight_dt <= current_dt)
+                {
+                    continue;
+                }
+                let available = self.available_seats_per_class(&date, flight);
+                let cheapest = flight
+                    .seat_classes
+                    .iter()
+                    .zip(&available)
+                    .filter(|(_, &seats_count)| seats_count > 0)
+                    .map(|(seat_class, _)| seat_class.price + flight.fuel_surcharge)
+                    .min();
+                if let Some(price) = cheapest {
+                    return self.msg_fmt(
+                        "next-available.found",
+                        &[
+                            &flight.flight_id,
+                            &date,
+                            &flight.departure_time,
+                            &flight.arrival_time,
+                            &price,
+                        ],
+                    );
+                }
+            }
+        }
+
+        self.msg("next-available.none")
+    }
+
+    fn matching_flights(
+        &self,
+        date: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+    ) -> Vec<&Flight> {
+        let mut matching_flights = vec![];
+
+        for flight in self.flights.values() {
+            if flight.departure_airport == departure_airport
+                && flight.arrival_airport == arrival_airport
+                && flight.operates_on(date)
+            {
+                matching_flights.push((flight.departure_time.clone(), flight.flight_id, flight));
+            }
+        }
+
+        matching_flights.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+        matching_flights
+            .into_iter()
+            .map(|(_, _, flight)| flight)
+            .collect()
+    }
+
+    fn available_seats_per_class(&self, date: &str, flight: &Flight) -> Vec<u32> {
+        let mut available = vec![];
+        for (i, seat_class) in flight.seat_classes.iter().enumerate() {
+            let mut seats_count = 0;
+            let start_row = if i == 0 {
+                1
+            } else {
+                flight.seat_classes[i - 1].column + 1
+            };
+            for row in start_row..=seat_class.column {
+                for seat_type in &SeatType::variants() {
+                    let seat_id = format!("{}{}", row, seat_type.as_char());
+                    if !self.is_seat_reserved(date, flight.flight_id, &seat_id) {
+                        seats_count += 1;
+                    }
+                }
+            }
+            available.push(seats_count);
+        }
+        available
+    }
+
+    fn process_block_user(&mut self, admin_token: &str, user_id: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("block-user.unauthorized_operation");
+        }
+
+        if !self.blocked_users.insert(user_id.to_string()) {
+            return self.msg("block-user.already_blocked");
+        }
+
+        self.msg("block-user.success")
+    }
+
+    fn process_unblock_user(&mut self, admin_token: &str, user_id: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("unblock-user.unauthorized_operation");
+        }
+
+        if !self.blocked_users.remove(user_id) {
+            return self.msg("unblock-user.user_not_blocked");
+        }
+
+        self.msg("unblock-user.success")
+    }
+
+    fn process_list_blocked_users(&self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("list-blocked-users.unauthorized_operation");
+        }
+
+        let mut users: Vec<&String> = self.blocked_users.iter().collect();
+        users.sort();
+
+        let mut result = vec![format!("list-blocked-users: {}", users.len())];
+        for user_id in users {
+            result.push(user_id.clone());
+        }
+
+        result.join("\n")
+    }
+
+    fn process_start_auction(
+        &mut self,
+        current_datetime: &str,
+        admin_token: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+        duration_minutes: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("start-auction.unauthorized_operation");
+        }
+
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("start-auction.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("start-auction.flight_not_operating_on_date");
+        }
+
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("start-auction.invalid_datetime"),
+        };
+
+        let flight_dt = match self.get_flight_datetime(date, flight) {
+            Some(dt) => dt,
+            None => return self.msg("start-auction.invalid_flight_datetime"),
+        };
+
+        if flight.get_seat_class(seat_id).is_none() {
+            return self.msg("start-auction.invalid_seat_id");
+        }
+
+        let ends_at = current_dt + Duration::minutes(duration_minutes as i64);
+        if self.is_too_late(ends_at, flight_dt) {
+            return self.msg("start-auction.too_late");
+        }
+
+        if self.is_seat_reserved(date, flight_id, seat_id) {
+            return self.msg("start-auction.already_reserved");
+        }
+
+        let key = self.seat_key(date, flight_id, seat_id);
+        if self.active_auctions.contains_key(&key) {
+            return self.msg("start-auction.auction_already_active");
+        }
+
+        self.active_auctions.insert(
+            key,
+            AuctionSeat {
+                date: date.to_string(),
+                flight_id,
+                seat_id: seat_id.to_string(),
+                bids: vec![],
+                ends_at,
+            },
+        );
+        self.publish_seat_event(SeatEventType::Blocked, date, flight_id, seat_id);
+
+        self.msg_fmt("start-auction.ends_at", &[&ends_at.format("%Y/%m/%d-%H:%M:%S")])
+    }
+
+    fn process_bid(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+        amount: u32,
+    ) -> String {
+        if self.blocked_users.contains(user_id) {
+            return self.msg("bid.user_blocked");
+        }
+
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("bid.invalid_datetime"),
+        };
+
+        let key = self.seat_key(date, flight_id, seat_id);
+        let auction = match self.active_auctions.get_mut(&key) {
+            Some(auction) => auction,
+            None => return self.msg("bid.auction_not_found"),
+        };
+
+        if current_dt >= auction.ends_at {
+            return self.msg("bid.auction_closed");
+        }
+
+        let (_, base_price) = self
+            .flights
+            .get(&flight_id)
+            .and_then(|flight| flight.get_seat_class(seat_id))
+            .unwrap();
+        if amount < base_price {
+            return self.msg("bid.below_base_price");
+        }
+
+        if let Some(&(_, highest)) = auction.bids.iter().max_by_key(|bid| bid.1) {
+            if amount <= highest {
+                return self.msg("bid.bid_too_low");
+            }
+        }
+
+        auction.bids.push((user_id.to_string(), amount));
+        self.msg("bid.success")
+    }
+
+    fn process_close_auction(
+        &mut self,
+        current_datetime: &str,
+        admin_token: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("close-auction.unauthorized_operation");
+        }
+
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("close-auction.invalid_datetime"),
+        };
+
+        let key = self.seat_key(date, flight_id, seat_id);
+        match self.active_auctions.get(&key) {
+            Some(auction) if current_dt < auction.ends_at => {
+                return self.msg("close-auction.auction_still_running");
+            }
+            Some(_) => {}
+            None => return self.msg("close-auction.auction_not_found"),
+        }
+
+        let auction = self.active_auctions.remove(&key).unwrap();
+        let (winner, amount) = match auction.bids.iter().max_by_key(|bid| bid.1) {
+            Some((user_id, amount)) => (user_id.clone(), *amount),
+            None => {
+                self.publish_seat_event(
+                    SeatEventType::Freed,
+                    &auction.date,
+                    auction.flight_id,
+                    &auction.seat_id,
+                );
+                return self.msg("close-auction.no_bids");
+            }
+        };
+
+        let mut losers: Vec<&String> = auction
+            .bids
+            .iter()
+            .map(|(user_id, _)| user_id)
+            .filter(|user_id| **user_id != winner)
+            .collect();
+        losers.sort();
+        losers.dedup();
+        let lost_notice = self.msg_fmt(
+            "notice.auction_lost",
+            &[&auction.date, &auction.flight_id, &auction.seat_id],
+        );
+        for loser in losers {
+            self.auction_notices
+                .entry(loser.clone())
+                .or_default()
+                .push(lost_notice.clone());
+        }
+
+        let reservation_id = self.create_reservation(
+            &winner,
+            &auction.date,
+            auction.flight_id,
+            &auction.seat_id,
+            amount,
+        );
+        format!("close-auction: {} {} {}", winner, reservation_id, amount)
+    }
+
+    fn process_set_alert_prefs(&mut self, user_id: &str, prefs_json: &str) -> String {
+        let prefs = match JsonValue::parse(prefs_json) {
+            Some(prefs @ JsonValue::Object(_)) => prefs,
+            _ => return self.msg("set-alert-prefs.invalid_prefs"),
+        };
+
+        let flag = |key: &str| match prefs.get(key) {
+            Some(value) => value.as_bool(),
+            None => Some(false),
+        };
+        let confirmation = flag("confirmation");
+        let cancellation_notice = flag("cancellation_notice");
+        let reminder_hours_before = match prefs.get("reminder_hours_before") {
+            Some(JsonValue::Array(items)) => items.iter().map(JsonValue::as_u32).collect(),
+            Some(_) => None,
+            None => Some(vec![]),
+        };
+
+        let (confirmation, reminder_hours_before, cancellation_notice) =
+            match (confirmation, reminder_hours_before, cancellation_notice) {
+                (Some(c), Some(r), Some(n)) => (c, r, n),
+                _ => return self.msg("set-alert-prefs.invalid_prefs"),
+            };
+
+        self.alert_prefs.insert(
+            user_id.to_string(),
+            AlertPreference {
+                user_id: user_id.to_string(),
+                confirmation,
+                reminder_hours_before,
+                cancellation_notice,
+            },
+        );
+
+        self.msg("set-alert-prefs.success")
+    }
+
+    fn process_get_due_reminders(&self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("due-reminders.invalid_datetime"),
+        };
+
+        let mut reservation_ids: Vec<&u32> = self.reservations.keys().collect();
+        reservation_ids.sort();
+
+        let mut reminders = vec![];
+        for reservation_id in reservation_ids {
+            let reservation = &self.reservations[reservation_id];
+            if reservation.is_cancelled {
+                continue;
+            }
+            let prefs = match self.alert_prefs.get(&reservation.user_id) {
+                Some(prefs) => prefs,
+                None => continue,
+            };
+            let flight = self.flights.get(&reservation.flight_id).unwrap();
+            let flight_dt = match self.get_flight_datetime(&reservation.date, flight) {
+                Some(dt) => dt,
+                None => continue,
+            };
+            if flight_dt <= current_dt {
+                continue;
+            }
+
+            let until_departure = flight_dt - current_dt;
+            let due = prefs.reminder_hours_before.iter().find(|&&hours| {
+                (until_departure - Duration::hours(hours as i64)).num_seconds().abs() <= 3600
+            });
+            if let Some(hours) = due {
+                reminders.push(self.msg_fmt(
+                    "due-reminders.reminder",
+                    &[
+                        &reservation.reservation_id,
+                        &prefs.user_id,
+                        &reservation.date,
+                        &reservation.flight_id,
+                        &reservation.seat_id,
+                        hours,
+                    ],
+                ));
+            }
+        }
+
+        let mut result = vec![format!("due-reminders: {}", reminders.len())];
+        result.extend(reminders);
+        result.join("\n")
+    }
+
+    fn process_top_flights(&self, top_n: u32, date_from: &str, date_to: &str) -> String {
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("top-flights.invalid_date"),
+        };
+        if from > to {
+            return self.msg("top-flights.invalid_range");
+        }
+
+        let mut counts: HashMap<u32, u32> = HashMap::new();
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled {
+                continue;
+            }
+            match self.parse_date(&reservation.date) {
+                Some(date) if from <= date && date <= to => {
+                    *counts.entry(reservation.flight_id).or_insert(0) += 1;
+                }
+                _ => {}
+            }
+        }
+
+        let mut ranking: Vec<(u32, u32)> = counts.into_iter().collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        if top_n > 0 {
+            ranking.truncate(top_n as usize);
+        }
+
+        let mut result = vec![format!("top-flights: {}", ranking.len())];
+        for (flight_id, count) in ranking {
+            result.push(self.msg_fmt("top-flights.entry", &[&flight_id, &count]));
+        }
+
+        result.join("\n")
+    }
+
+    fn process_forecast_demand(
+        &self,
+        current_datetime: &str,
+        flight_id: u32,
+        date: &str,
+    ) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("forecast-demand.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("forecast-demand.flight_not_operating_on_date");
+        }
+        let current_date = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt.date(),
+            Err(_) => return self.msg("forecast-demand.invalid_datetime"),
+        };
+        let flight_date = match self.parse_date(date) {
+            Some(flight_date) => flight_date,
+            None => return self.msg("forecast-demand.invalid_date"),
+        };
+        let days_until = (flight_date - current_date).num_days();
+        if days_until < 14 {
+            return self.msg("forecast-demand.too_close_to_departure");
+        }
+
+        let booking_dates: Vec<NaiveDate> = self
+            .price_history
+            .iter()
+            .filter(|entry| entry.flight_id == flight_id && entry.date == date)
+            .filter_map(|entry| {
+                NaiveDateTime::parse_from_str(&entry.timestamp, "%Y/%m/%d-%H:%M:%S").ok()
+            })
+            .map(|dt| dt.date())
+            .filter(|&booked| booked < current_date)
+            .collect();
+        let window_start = match booking_dates.iter().min() {
+            Some(&first) => first.max(current_date - Duration::days(7)),
+            None => return self.msg("forecast-demand.insufficient_data"),
+        };
+        let days = (current_date - window_start).num_days();
+        if days < 3 {
+            return self.msg("forecast-demand.insufficient_data");
+        }
+
+        let counts: Vec<f64> = (0..days)
+            .map(|offset| {
+                let day = window_start + Duration::days(offset);
+                booking_dates.iter().filter(|&&booked| booked == day).count() as f64
+            })
+            .collect();
+        let n = days as f64;
+        let sum_x: f64 = (0..days).map(|x| x as f64).sum();
+        let sum_xx: f64 = (0..days).map(|x| (x * x) as f64).sum();
+        let sum_y: f64 = counts.iter().sum();
+        let sum_xy: f64 = counts.iter().enumerate().map(|(x, y)| x as f64 * y).sum();
+        let slope = (n * sum_xy - sum_x * sum_y) / (n * sum_xx - sum_x * sum_x);
+        let intercept = (sum_y - slope * sum_x) / n;
+        let predicted: f64 = (days..days + days_until)
+            .map(|x| (intercept + slope * x as f64).max(0.0))
+            .sum();
+
+        let booked = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.flight_id == flight_id
+                    && reservation.date == date
+            })
+            .count() as f64;
+        let capacity = flight.seat_count() as f64;
+        let occupancy = ((booked + predicted) / capacity * 100.0).min(100.0);
+        let confidence = if days >= 7 { "high" } else { "medium" };
+
+        format!(
+            "forecast-demand: flight {} date {} predicted_occupancy={:.1}% confidence={}",
+            flight_id, date, occupancy, confidence
+        )
+    }
+
+    fn load_factor(&self, flight_id: u32, date_from: NaiveDate, date_to: NaiveDate) -> Option<f32> {
+        let flight = self.flights.get(&flight_id)?;
+        let mut operating_dates = HashSet::new();
+        let mut date = date_from;
+        while date <= date_to {
+            let date_str = date.format("%Y/%m/%d").to_string();
+            if flight.operates_on(&date_str) {
+                operating_dates.insert(date_str);
+            }
+            date += Duration::days(1);
+        }
+        let capacity = flight.seat_count() * operating_dates.len() as u32;
+        if capacity == 0 {
+            return None;
+        }
+
+        let carried = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.flight_id == flight_id
+                    && operating_dates.contains(&reservation.date)
+            })
+            .count();
+        Some(carried as f32 / capacity as f32)
+    }
+
+    fn process_load_factor_report(&self, date_from: &str, date_to: &str) -> String {
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("load-factor-report.invalid_date"),
+        };
+        if from > to {
+            return self.msg("load-factor-report.invalid_range");
+        }
+        if (to - from).num_days() >= 366 {
+            return self.msg("load-factor-report.range_too_long");
+        }
+
+        let mut ranking: Vec<(&Flight, f32)> = self
+            .flights
+            .values()
+            .filter_map(|flight| Some((flight, self.load_factor(flight.flight_id, from, to)?)))
+            .collect();
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.flight_id.cmp(&b.0.flight_id)));
+
+        let mut result = vec![format!("load-factor-report: {} flights", ranking.len())];
+        for (flight, load_factor) in ranking {
+            result.push(format!(
+                "{} {}->{} {:.1}%",
+                flight.flight_id,
+                flight.departure_airport,
+                flight.arrival_airport,
+                load_factor * 100.0
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn process_class_revenue_breakdown(
+        &self,
+        flight_id: u32,
+        class_index: u32,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("class-revenue-breakdown.flight_not_found"),
+        };
+        if class_index == 0 || class_index as usize > flight.seat_classes.len() {
+            return self.msg("class-revenue-breakdown.invalid_class");
+        }
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("class-revenue-breakdown.invalid_date"),
+        };
+        if from > to {
+            return self.msg("class-revenue-breakdown.invalid_range");
+        }
+        if (to - from).num_days() >= 366 {
+            return self.msg("class-revenue-breakdown.range_too_long");
+        }
+
+        let mut rows: Vec<(u32, u64, u64)> = vec![(0, 0, 0); (to - from).num_days() as usize + 1];
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled
+                || reservation.flight_id != flight_id
+                || self.seat_class_index_of(reservation) != Some(class_index)
+            {
+                continue;
+            }
+            let row = match self.parse_date(&reservation.date) {
+                Some(date) if date >= from && date <= to => {
+                    &mut rows[(date - from).num_days() as usize]
+                }
+                _ => continue,
+            };
+            let discount = (reservation.fare_discount + reservation.group_discount) as u64;
+            row.0 += 1;
+            row.1 += reservation.base_price as u64 + discount;
+            row.2 += discount;
+        }
+
+        let format_row = |label: &str, (seats, revenue, discounts): (u32, u64, u64)| {
+            let net = revenue - discounts;
+            let average = if seats == 0 { 0 } else { net / seats as u64 };
+            format!(
+                "{:<10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                label, seats, revenue, discounts, net, average
+            )
+        };
+        let mut result = vec![
+            format!(
+                "class-revenue-breakdown: flight {} class {} {}-{}",
+                flight_id, class_index, date_from, date_to
+            ),
+            format!(
+                "{:<10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                "date", "seats", "revenue", "discounts", "net", "average"
+            ),
+        ];
+        let mut totals = (0, 0, 0);
+        for (date, row) in from.iter_days().zip(&rows) {
+            result.push(format_row(&date.format("%Y/%m/%d").to_string(), *row));
+            totals.0 += row.0;
+            totals.1 += row.1;
+            totals.2 += row.2;
+        }
+        result.push(format_row("total", totals));
+        result.join("\n")
+    }
+
+    fn process_retroactive_price_fix(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        class_index: u32,
+        date_from: &str,
+        date_to: &str,
+        correct_price: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("retroactive-fix.unauthorized_operation");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("retroactive-fix.flight_not_found"),
+        };
+        if class_index == 0 || class_index as usize > flight.seat_classes.len() {
+            return self.msg("retroactive-fix.invalid_class");
+        }
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("retroactive-fix.invalid_date"),
+        };
+        if from > to {
+            return self.msg("retroactive-fix.invalid_range");
+        }
+
+        let mut reservation_ids: Vec<u32> = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.flight_id == flight_id
+                    && self.seat_class_index_of(reservation) == Some(class_index)
+                    && self
+                        .parse_date(&reservation.date)
+                        .is_some_and(|date| date >= from && date <= to)
+            })
+            .map(|reservation| reservation.reservation_id)
+            .collect();
+        reservation_ids.sort();
+
+        let mut total_delta: i64 = 0;
+        for &reservation_id in &reservation_ids {
+            let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+            let original_price = *reservation.original_price.get_or_insert(reservation.base_price);
+            let previous_price = reservation.base_price;
+            reservation.base_price = correct_price;
+            reservation.total_price = correct_price + reservation.service_fee;
+            total_delta += correct_price as i64 - original_price as i64;
+            self.record_audit(
+                "admin",
+                "retroactive-fix",
+                format!(
+                    "reservation_id={} original_price={} previous_price={} new_price={}",
+                    reservation_id, original_price, previous_price, correct_price
+                ),
+            );
+        }
+        self.msg_fmt("retroactive-fix.success", &[&reservation_ids.len(), &total_delta])
+    }
+
+    fn process_simulate_price_change(
+        &self,
+        class_index: u32,
+        delta_pct: i32,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        if class_index == 0 {
+            return self.msg("simulate-price-change.invalid_class");
+        }
+        if delta_pct < -100 {
+            return self.msg("simulate-price-change.invalid_percent");
+        }
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("simulate-price-change.invalid_date"),
+        };
+        if from > to {
+            return self.msg("simulate-price-change.invalid_range");
+        }
+
+        let mut actual: i64 = 0;
+        let mut simulated: i64 = 0;
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled
+                || self.seat_class_index_of(reservation) != Some(class_index)
+                || !self
+                    .parse_date(&reservation.date)
+                    .is_some_and(|date| date >= from && date <= to)
+            {
+                continue;
+            }
+            let price = reservation.base_price as i64;
+            actual += price;
+            simulated += price * (100 + delta_pct as i64) / 100;
+        }
+        let delta = simulated - actual;
+        let delta_share = if actual == 0 {
+            0.0
+        } else {
+            delta as f64 / actual as f64 * 100.0
+        };
+        let header = format!("simulate-price-change: class {} {}%", class_index, delta_pct);
+        format!(
+            "{}\nactual_revenue={} simulated_revenue={} delta={} ({:.1}%)",
+            header, actual, simulated, delta, delta_share
+        )
+    }
+
+    fn process_find_empty_flights(&self, date_from: &str, date_to: &str) -> String {
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("empty-flights.invalid_date"),
+        };
+        if from > to {
+            return self.msg("empty-flights.invalid_range");
+        }
+        if (to - from).num_days() >= 366 {
+            return self.msg("empty-flights.range_too_long");
+        }
+
+        let dates: Vec<String> = from
+            .iter_days()
+            .take_while(|date| *date <= to)
+            .map(|date| date.format("%Y/%m/%d").to_string())
+            .collect();
+        let mut flights: Vec<&Flight> = self.flights.values().collect();
+        flights.sort_by_key(|flight| flight.flight_id);
+
+        let mut empty = vec![];
+        for flight in flights {
+            let operating_dates: Vec<&String> =
+                dates.iter().filter(|date| flight.operates_on(date)).collect();
+            if operating_dates.is_empty() {
+                continue;
+            }
+            let has_reservations = self.reservations.values().any(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.flight_id == flight.flight_id
+                    && operating_dates.contains(&&reservation.date)
+            });
+            if !has_reservations {
+                empty.push(format!(
+                    "{} {}->{} ({} empty dates)",
+                    flight.flight_id,
+                    flight.departure_airport,
+                    flight.arrival_airport,
+                    operating_dates.len()
+                ));
+            }
+        }
+
+        let mut result = vec![format!("empty-flights: {} flights found", empty.len())];
+        result.extend(empty);
+        result.join("\n")
+    }
+
+    fn process_route_statistics(
+        &self,
+        departure_airport: u32,
+        arrival_airport: u32,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("route-statistics.invalid_date"),
+        };
+        if from > to {
+            return self.msg("route-statistics.invalid_range");
+        }
+
+        let route_flights: Vec<&Flight> = self
+            .flights
+            .values()
+            .filter(|flight| {
+                flight.departure_airport == departure_airport
+                    && flight.arrival_airport == arrival_airport
+            })
+            .collect();
+        if route_flights.is_empty() {
+            return self.msg("route-statistics.no_data");
+        }
+
+        let mut total_reservations = 0;
+        let mut total_revenue: u64 = 0;
+        let mut class_counts: HashMap<u32, u32> = HashMap::new();
+        let mut reserved_per_flight_date: HashMap<(u32, NaiveDate), u32> = HashMap::new();
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled {
+                continue;
+            }
+            let flight = match route_flights
+                .iter()
+                .find(|flight| flight.flight_id == reservation.flight_id)
+            {
+                Some(flight) => flight,
+                None => continue,
+            };
+            let date = match self.parse_date(&reservation.date) {
+                Some(date) if from <= date && date <= to => date,
+                _ => continue,
+            };
+
+            total_reservations += 1;
+            total_revenue += reservation.total_price as u64;
+            if let Some((seat_class, _)) = flight.get_seat_class(&reservation.seat_id) {
+                *class_counts.entry(seat_class).or_insert(0) += 1;
+            }
+            *reserved_per_flight_date
+                .entry((reservation.flight_id, date))
+                .or_insert(0) += 1;
+        }
+
+        let days = (to - from).num_days() + 1;
+        let mut occupancy_sum = 0.0;
+        let mut occupancy_samples = 0;
+        for flight in &route_flights {
+            let capacity = flight.seat_count();
+            if capacity == 0 {
+                continue;
+            }
+            for offset in 0..days {
+                let date = from + Duration::days(offset);
+                if !flight.operates_on(&date.format("%Y/%m/%d").to_string()) {
+                    continue;
+                }
+                let reserved = reserved_per_flight_date
+                    .get(&(flight.flight_id, date))
+                    .copied()
+                    .unwrap_or(0);
+                occupancy_sum += reserved as f64 / capacity as f64;
+                occupancy_samples += 1;
+            }
+        }
+        let avg_occupancy = if occupancy_samples == 0 {
+            0.0
+        } else {
+            occupancy_sum / occupancy_samples as f64 * 100.0
+        };
+
+        let popular_class = class_counts
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map_or("none".to_string(), |(seat_class, _)| format!("class {}", seat_class));
+
+        [
+            format!("route-statistics: {}->{}", departure_airport, arrival_airport),
+            format!("total_reservations: {}", total_reservations),
+            format!("total_revenue: {}", total_revenue),
+            format!("avg_occupancy: {:.1}%", avg_occupancy),
+            format!("popular_class: {}", popular_class),
+        ]
+        .join("\n")
+    }
+
+    fn process_checkin(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+    ) -> String {
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("checkin.reservation_not_found"),
+        };
+
+        if reservation.user_id != user_id {
+            return self.msg("checkin.unauthorized_operation");
+        }
+
+        if reservation.is_checked_in {
+            return self.msg("checkin.already_checked_in");
+        }
+
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("checkin.invalid_datetime"),
+        };
+
+        let flight = self.flights.get(&reservation.flight_id).unwrap();
+        let flight_dt = match self.get_flight_datetime(&reservation.date, flight) {
+            Some(dt) => dt,
+            None => return self.msg("checkin.invalid_flight_datetime"),
+        };
+
+        if current_dt >= flight_dt {
+            return self.msg("checkin.too_late");
+        }
+
+        let earned = reservation.total_price / FARE_PER_MILE;
+        self.reservations.get_mut(&reservation_id).unwrap().is_checked_in = true;
+        if earned > 0 {
+            self.miles
+                .entry(user_id.to_string())
+                .or_default()
+                .entries
+                .push(MilesEntry {
+                    amount: earned,
+                    earned_at: current_dt.date(),
+                    source_reservation_id: reservation_id,
+                });
+        }
+        self.msg("checkin.success")
+    }
+
+    fn process_auto_cancel_no_checkin(&mut self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("auto-cancel.invalid_datetime"),
+        };
+
+        let mut reservation_ids: Vec<u32> = self.reservations.keys().copied().collect();
+        reservation_ids.sort();
+
+        let mut timed_out = 0;
+        for reservation_id in reservation_ids {
+            let reservation = &self.reservations[&reservation_id];
+            if reservation.is_cancelled || reservation.is_checked_in {
+                continue;
+            }
+            let flight = self.flights.get(&reservation.flight_id).unwrap();
+            let flight_dt = match self.get_flight_datetime(&reservation.date, flight) {
+                Some(dt) => dt,
+                None => continue,
+            };
+            if current_dt < flight_dt - Duration::hours(1) || current_dt >= flight_dt {
+                continue;
+            }
+
+            self.record_event(|| SystemEvent::ReservationCancelled { reservation_id });
+            let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+            reservation.is_cancelled = true;
+            reservation.is_timeout_cancelled = true;
+            let user_id = reservation.user_id.clone();
+            let date = reservation.date.clone();
+            let flight_id = reservation.flight_id;
+            let seat_id = reservation.seat_id.clone();
+            let refund = self.refundable_amount(&self.reservations[&reservation_id]);
+
+            self.unreserve_seat(&date, flight_id, &seat_id);
+            self.remove_itinerary_if_cancelled(reservation_id);
+            self.notify_reservation_cancelled(reservation_id);
+            self.issue_voucher(&user_id, reservation_id, refund);
+            timed_out += 1;
+        }
+
+        self.msg_fmt("auto-cancel.timed_out", &[&timed_out])
+    }
+
+    fn process_get_vouchers(&self, user_id: &str) -> String {
+        let mut vouchers: Vec<&RefundVoucher> = self
+            .vouchers
+            .values()
+            .filter(|voucher| voucher.user_id == user_id)
+            .collect();
+        vouchers.sort_by_key(|voucher| voucher.voucher_id);
+
+        let mut result = vec![format!("get-vouchers: {}", vouchers.len())];
+        for voucher in vouchers {
+            result.push(format!(
+                "voucher id: {}, amount: {}, reservation: {}",
+                voucher.voucher_id, voucher.amount, voucher.reservation_id
+            ));
+        }
+
+        result.join("\n")
+    }
+
+    fn process_flexible_flight_search(
+        &self,
+        current_datetime: &str,
+        target_date: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+        flex_days: u32,
+    ) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("flexible-search.invalid_datetime"),
+        };
+        let target = match self.parse_date(target_date) {
+            Some(date) => date,
+            None => return self.msg("flexible-search.invalid_date"),
+        };
+        let flex_days = flex_days.min(30) as i64;
+
+        let mut result = vec![];
+        for offset in -flex_days..=flex_days {
+            let date = (target + Duration::days(offset)).format("%Y/%m/%d").to_string();
+
+            let mut options = vec![];
+            for flight in self.matching_flights(&date, departure_airport, arrival_airport) {
+                match self.get_flight_datetime(&date, flight) {
+                    Some(flight_dt) if !self.is_too_late(current_dt, flight_dt) => {}
+                    _ => continue,
+                }
+                let available = self.available_seats_per_class(&date, flight);
+                let min_price = flight
+                    .seat_classes
+                    .iter()
+                    .zip(available)
+                    .filter(|(_, seats)| *seats > 0)
+                    .map(|(seat_class, _)| seat_class.price)
+                    .min();
+                if let Some(min_price) = min_price {
+                    options.push((min_price, flight.flight_id));
+                }
+            }
+            if options.is_empty() {
+                continue;
+            }
+
+            options.sort();
+            let options: Vec<String> = options
+                .iter()
+                .map(|(min_price, flight_id)| format!("{} {}", flight_id, min_price))
+                .collect();
+            result.push(format!("{}: {}", date, options.join(", ")));
+        }
+
+        let mut output = vec![self.msg_fmt("flexible-search.found", &[&result.len()])];
+        output.extend(result);
+        output.join("\n")
+    }
+
+    fn process_find_adjacent_seats(
+        &self,
+        _current_datetime: &str,
+        date: &str,
+        flight_id: u32,
+        num_seats: u32,
+        same_row: bool,
+    ) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("adjacent-seats.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("adjacent-seats.flight_not_operating_on_date");
+        }
+        if num_seats == 0 {
+            return self.msg("adjacent-seats.invalid_num_seats");
+        }
+        let num_seats = num_seats as usize;
+
+        let mut groups = vec![];
+        let mut start_row = 1;
+        for seat_class in &flight.seat_classes {
+            let mut class_seats = vec![];
+            for row in start_row..=seat_class.column {
+                let row_seats: Vec<(u32, String)> = SeatType::variants()
+                    .iter()
+                    .map(|seat_type| (row, format!("{}{}", row, seat_type.as_char())))
+                    .collect();
+                if same_row {
+                    self.collect_free_runs(date, flight_id, &row_seats, num_seats, &mut groups);
+                } else {
+                    class_seats.extend(row_seats);
+                }
+            }
+            if !same_row {
+                self.collect_free_runs(date, flight_id, &class_seats, num_seats, &mut groups);
+            }
+            start_row = seat_class.column + 1;
+        }
+
+        if groups.is_empty() {
+            return self.msg("adjacent-seats.none");
+        }
+        let mut groups: Vec<(f32, u32, Vec<String>)> = groups
+            .into_iter()
+            .map(|(row, seat_ids)| {
+                let weight = seat_ids.iter().map(|seat_id| self.seat_weight(seat_id)).sum();
+                (weight, row, seat_ids)
+            })
+            .collect();
+        groups.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut result = vec![format!("adjacent-seats: {} found", groups.len())];
+        for (_, row, seat_ids) in groups {
+            result.push(format!("row {} seats {}", row, seat_ids.join(",")));
+        }
+        result.join("\n")
+    }
+
+    fn seat_weight(&self, seat_id: &str) -> f32 {
+        let seat_type = seat_id.chars().last().and_then(SeatType::from_char);
+        self.seat_weights
+            .iter()
+            .find(|entry| Some(&entry.seat_type) == seat_type.as_ref())
+            .map_or(0.0, |entry| entry.weight)
+    }
+
+    fn process_set_seat_weights(&mut self, admin_token: &str, weights_csv: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-seat-weights.unauthorized_operation");
+        }
+
+        let mut updates = vec![];
+        for token in weights_csv.split(',') {
+            let (seat_type, weight) = match token.trim().split_once(':') {
+                Some((seat_type, weight)) => (seat_type, weight),
+                None => return self.msg("set-seat-weights.invalid_weights"),
+            };
+            let mut chars = seat_type.chars();
+            let seat_type = match (chars.next().and_then(SeatType::from_char), chars.next()) {
+                (Some(seat_type), None) => seat_type,
+                _ => return self.msg("set-seat-weights.invalid_weights"),
+            };
+            match weight.parse::<f32>() {
+                Ok(weight) if weight.is_finite() && weight >= 0.0 => {
+                    updates.push((seat_type, weight))
+                }
+                _ => return self.msg("set-seat-weights.invalid_weights"),
+            }
+        }
+
+        for (seat_type, weight) in updates {
+            if let Some(entry) =
+                self.seat_weights.iter_mut().find(|entry| entry.seat_type == seat_type)
+            {
+                entry.weight = weight;
+            }
+        }
+        let summary: Vec<String> = self
+            .seat_weights
+            .iter()
+            .map(|entry| format!("{}:{:.2}", entry.seat_type.as_char(), entry.weight))
+            .collect();
+        self.record_audit("admin", "set-seat-weights", summary.join(","));
+        self.msg_fmt("set-seat-weights.success", &[&summary.join(",")])
+    }
+
+    fn collect_free_runs(
+        &self,
+        date: &str,
+        flight_id: u32,
+        seats: &[(u32, String)],
+        num_seats: usize,
+        groups: &mut Vec<(u32, Vec<String>)>,
+    ) {
+        let flight = self.flights.get(&flight_id).unwrap();
+        for window in seats.windows(num_seats) {
+            if window.iter().all(|(_, seat_id)| {
+                flight.seat_type_allowed(seat_id) && !self.is_seat_reserved(date, flight_id, seat_id)
+            }) {
+                let seat_ids = window.iter().map(|(_, seat_id)| seat_id.clone()).collect();
+                groups.push((window[0].0, seat_ids));
+            }
+        }
+    }
+
+    fn process_clone_flight_schedule(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        start_date: &str,
+        end_date: &str,
+        day_mask: u8,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("clone-schedule.unauthorized_operation");
+        }
+
+        let source = match self.flights.get(&flight_id) {
+            Some(flight) => flight.clone(),
+            None => return self.msg("clone-schedule.flight_not_found"),
+        };
+
+        let (start, end) = match (self.parse_date(start_date), self.parse_date(end_date)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return self.msg("clone-schedule.invalid_date"),
+        };
+        if start > end {
+            return self.msg("clone-schedule.invalid_range");
+        }
+        if (end - start).num_days() >= 366 {
+            return self.msg("clone-schedule.range_too_long");
+        }
+
+        let mut next_flight_id = self.flights.keys().max().copied().unwrap_or(0) + 1;
+        let mut cloned = vec![];
+        let mut date = start;
+        while date <= end {
+            if day_mask & (1 << date.weekday().num_days_from_monday()) != 0 {
+                let service_date = date.format("%Y/%m/%d").to_string();
+                let mut flight = source.clone();
+                flight.flight_id = next_flight_id;
+                flight.service_date = Some(service_date.clone());
+                self.add_flight(flight);
+                cloned.push(format!("{} {}", service_date, next_flight_id));
+                next_flight_id += 1;
+            }
+            date += Duration::days(1);
+        }
+
+        let mut result = vec![self.msg_fmt("clone-schedule.cloned", &[&flight_id, &cloned.len()])];
+        result.extend(cloned);
+        result.join("\n")
+    }
+
+    fn process_create_itinerary(&mut self, user_id: &str, reservation_ids_csv: &str) -> String {
+        let mut reservation_ids = vec![];
+        for id in reservation_ids_csv.split(',') {
+            match id.trim().parse::<u32>() {
+                Ok(id) if !reservation_ids.contains(&id) => reservation_ids.push(id),
+                _ => return self.msg("create-itinerary.invalid_reservation_ids"),
+            }
+        }
+        if reservation_ids.len() < 2 {
+            return self.msg("create-itinerary.at_least_two_reservations_required");
+        }
+
+        for reservation_id in &reservation_ids {
+            let reservation = match self.reservations.get(reservation_id) {
+                Some(reservation) if !reservation.is_cancelled => reservation,
+                _ => return self.msg("create-itinerary.reservation_not_found"),
+            };
+            if reservation.user_id != user_id {
+                return self.msg("create-itinerary.unauthorized_operation");
+            }
+            if self.itinerary_of(*reservation_id).is_some() {
+                return self.msg("create-itinerary.reservation_already_in_itinerary");
+            }
+        }
+
+        let itinerary_id = self.next_itinerary_id;
+        self.itineraries.insert(
+            itinerary_id,
+            Itinerary {
+                itinerary_id,
+                reservation_ids,
+                user_id: user_id.to_string(),
+            },
+        );
+        self.next_itinerary_id += 1;
+
+        format!("create-itinerary: {}", itinerary_id)
+    }
+
+    fn process_auto_create_itinerary(&mut self, user_id: &str, date: &str) -> String {
+        if self.parse_date(date).is_none() {
+            return self.msg("auto-create-itinerary.invalid_date");
+        }
+
+        let mut legs: Vec<(NaiveDateTime, NaiveDateTime, u32)> = vec![];
+        for reservation in self.reservations.values() {
+            if reservation.user_id != user_id
+                || reservation.date != date
+                || reservation.is_cancelled
+                || self.itinerary_of(reservation.reservation_id).is_some()
+            {
+                continue;
+            }
+            let flight = &self.flights[&reservation.flight_id];
+            if let (Some(departure), Some(arrival)) = (
+                self.get_flight_datetime(date, flight),
+                self.get_arrival_datetime(date, flight),
+            ) {
+                legs.push((departure, arrival, reservation.reservation_id));
+            }
+        }
+        legs.sort();
+
+        let mut used = vec![false; legs.len()];
+        let mut chains: Vec<Vec<u32>> = vec![];
+        for start in 0..legs.len() {
+            if used[start] {
+                continue;
+            }
+            used[start] = true;
+            let mut chain = vec![start];
+            loop {
+                let last = *chain.last().unwrap();
+                let inbound = &self.flights[&self.reservations[&legs[last].2].flight_id];
+                let ready_at = legs[last].1 + self.min_connection_time(inbound.arrival_airport);
+                let next = (last + 1..legs.len()).find(|&i| {
+                    let outbound = &self.flights[&self.reservations[&legs[i].2].flight_id];
+                    !used[i]
+                        && outbound.departure_airport == inbound.arrival_airport
+                        && legs[i].0 >= ready_at
+                });
+                match next {
+                    Some(i) => {
+                        used[i] = true;
+                        chain.push(i);
+                    }
+                    None => break,
+                }
+            }
+            if chain.len() >= 2 {
+                chains.push(chain.into_iter().map(|i| legs[i].2).collect());
+            }
+        }
+
+        let created = chains.len();
+        for reservation_ids in chains {
+            let itinerary_id = self.next_itinerary_id;
+            self.itineraries.insert(
+                itinerary_id,
+                Itinerary {
+                    itinerary_id,
+                    reservation_ids,
+                    user_id: user_id.to_string(),
+                },
+            );
+            self.next_itinerary_id += 1;
+        }
+
+        self.msg_fmt("auto-create-itinerary.success", &[&created])
+    }
+
+    fn process_set_min_connection_time(
+        &mut self,
+        admin_token: &str,
+        airport: u32,
+        minutes: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-min-connection-time.unauthorized_operation");
+        }
+
+        self.min_connection_times.insert(airport, minutes);
+        self.msg_fmt("set-min-connection-time.success", &[&airport, &minutes])
+    }
+
+    fn process_recode_airport(
+        &mut self,
+        admin_token: &str,
+        old_airport_id: u32,
+        new_airport_id: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("recode-airport.unauthorized_operation");
+        }
+        if old_airport_id == new_airport_id {
+            return self.msg("recode-airport.same_airport");
+        }
+        let serves = |flight: &Flight, airport: u32| {
+            flight.departure_airport == airport || flight.arrival_airport == airport
+        };
+        if self.flights.values().any(|flight| serves(flight, new_airport_id))
+            || self.min_connection_times.contains_key(&new_airport_id)
+        {
+            return self.msg("recode-airport.airport_in_use");
+        }
+
+        let mut updated = 0;
+        for flight in self.flights.values_mut() {
+            if !serves(flight, old_airport_id) {
+                continue;
+            }
+            if flight.departure_airport == old_airport_id {
+                flight.departure_airport = new_airport_id;
+            }
+            if flight.arrival_airport == old_airport_id {
+                flight.arrival_airport = new_airport_id;
+            }
+            updated += 1;
+        }
+        if updated == 0 {
+            return self.msg("recode-airport.airport_not_found");
+        }
+        if let Some(minutes) = self.min_connection_times.remove(&old_airport_id) {
+            self.min_connection_times.insert(new_airport_id, minutes);
+        }
+        self.search_cache
+            .entries
+            .retain(|(_, _, dep, arr), _| *dep != old_airport_id && *arr != old_airport_id);
+        self.record_audit(
+            "admin",
+            "recode-airport",
+            format!("old={} new={} flights={}", old_airport_id, new_airport_id, updated),
+        );
+        self.msg_fmt("recode-airport.success", &[&old_airport_id, &new_airport_id, &updated])
+    }
+
+    fn process_set_output_delimiter(&mut self, admin_token: &str, delimiter: char) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-output-delimiter.unauthorized_operation");
+        }
+        if !matches!(delimiter, ' ' | '|' | ',') {
+            return self.msg("set-output-delimiter.invalid_delimiter");
+        }
+
+        self.output_delimiter = delimiter;
+        self.clear_search_cache();
+        self.record_audit("admin", "set-output-delimiter", format!("delimiter={:?}", delimiter));
+        self.msg_fmt("set-output-delimiter.success", &[&format!("{:?}", delimiter)])
+    }
+
+    fn process_set_cache_ttl(&mut self, admin_token: &str, seconds: u64) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-cache-ttl.unauthorized_operation");
+        }
+
+        self.cache_ttl_seconds = seconds;
+        self.clear_search_cache();
+        self.record_audit("admin", "set-cache-ttl", format!("seconds={}", seconds));
+        self.msg_fmt("set-cache-ttl.success", &[&seconds])
+    }
+
+    fn process_clear_cache(&mut self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("clear-cache.unauthorized_operation");
+        }
+
+        let cleared = self.search_cache.entries.len();
+        self.clear_search_cache();
+        self.msg_fmt("clear-cache.success", &[&cleared])
+    }
+
+    fn process_merge_seat_classes(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        class_index_a: u32,
+        class_index_b: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("merge-classes.unauthorized_operation");
+        }
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("merge-classes.flight_not_found"),
+        };
+        if class_index_a == 0
+            || class_index_b != class_index_a + 1
+            || class_index_b as usize > flight.seat_classes.len()
+        {
+            return self.msg("merge-classes.invalid_class");
+        }
+
+        let a = class_index_a as usize - 1;
+        let start_row = match a {
+            0 => 1,
+            _ => flight.seat_classes[a - 1].column + 1,
+        };
+        let seats_per_row = SeatType::variants().len() as u64;
+        let rows_a = (flight.seat_classes[a].column + 1 - start_row) as u64;
+        let rows_b = (flight.seat_classes[a + 1].column - flight.seat_classes[a].column) as u64;
+        let weighted = rows_a * seats_per_row * flight.seat_classes[a].price as u64
+            + rows_b * seats_per_row * flight.seat_classes[a + 1].price as u64;
+        let new_price = (weighted / ((rows_a + rows_b) * seats_per_row)) as u32;
+
+        let class_b = flight.seat_classes.remove(a + 1);
+        let merged = &mut flight.seat_classes[a];
+        merged.column = class_b.column;
+        merged.price = new_price;
+        let seat_types_pair = (merged.allowed_seat_types.take(), class_b.allowed_seat_types);
+        merged.allowed_seat_types = match seat_types_pair {
+            (Some(mut seat_types), Some(other)) => {
+                for seat_type in other {
+                    if !seat_types.contains(&seat_type) {
+                        seat_types.push(seat_type);
+                    }
+                }
+                Some(seat_types)
+            }
+            _ => None,
+        };
+        self.clear_search_cache();
+
+        for reservation in self.reservations.values_mut() {
+            if reservation.flight_id != flight_id {
+                continue;
+            }
+            if let Some(class_index) = reservation.original_class_index.as_mut() {
+                if *class_index > class_index_a as usize {
+                    *class_index -= 1;
+                }
+            }
+        }
+        self.record_audit(
+            "admin",
+            "merge-classes",
+            format!(
+                "flight_id={} classes={},{} price={}",
+                flight_id, class_index_a, class_index_b, new_price
+            ),
+        );
+
+        self.msg_fmt(
+            "merge-classes.success",
+            &[&flight_id, &class_index_a, &class_index_b, &class_index_a, &new_price],
+        )
+    }
+
+    fn process_adjust_class_boundary(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        class_index: u32,
+        new_column: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("adjust-boundary.unauthorized_operation");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("adjust-boundary.flight_not_found"),
+        };
+        if class_index == 0 || class_index as usize > flight.seat_classes.len() {
+            return self.msg("adjust-boundary.invalid_class");
+        }
+
+        let i = class_index as usize - 1;
+        let lower = match i {
+            0 => 0,
+            _ => flight.seat_classes[i - 1].column,
+        };
+        let upper = match flight.seat_classes.get(i + 1) {
+            Some(next_class) => next_class.column - 1,
+            None => SEAT_ROWS,
+        };
+        let is_last_class = i + 1 == flight.seat_classes.len();
+        if new_column <= lower || new_column > upper || (is_last_class && new_column != upper) {
+            return self.msg("adjust-boundary.invalid_boundary");
+        }
+
+        let mut adjusted = flight.clone();
+        adjusted.seat_classes[i].column = new_column;
+        let mut reservation_ids: Vec<u32> = self
+            .reservations
+            .values()
+            .filter(|reservation| reservation.flight_id == flight_id && !reservation.is_cancelled)
+            .map(|reservation| reservation.reservation_id)
+            .collect();
+        reservation_ids.sort();
+
+        let mut reclassified = vec![];
+        for reservation_id in reservation_ids {
+            let seat_id = &self.reservations[&reservation_id].seat_id;
+            let (old_class, old_price) = flight.get_seat_class(seat_id).unwrap();
+            let (new_class, new_price) = match adjusted.get_seat_class(seat_id) {
+                Some(seat_class) => seat_class,
+                None => return self.msg("adjust-boundary.reservations_stranded"),
+            };
+            if new_class != old_class {
+                reclassified.push((reservation_id, new_price as i64 - old_price as i64));
+            }
+        }
+
+        let old_column = flight.seat_classes[i].column;
+        self.flights.get_mut(&flight_id).unwrap().seat_classes[i].column = new_column;
+        self.clear_search_cache();
+        for &(reservation_id, difference) in &reclassified {
+            self.reservations.get_mut(&reservation_id).unwrap().pending_fare_adjustment +=
+                difference;
+        }
+        self.record_audit(
+            "admin",
+            "adjust-boundary",
+            format!(
+                "flight_id={} class={} column={}->{} reclassified={}",
+                flight_id,
+                class_index,
+                old_column,
+                new_column,
+                reclassified.len()
+            ),
+        );
+
+        self.msg_fmt(
+            "adjust-boundary.success",
+            &[&flight_id, &class_index, &new_column, &reclassified.len()],
+        )
+    }
+
+    fn process_update_class_seat_types(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        class_index: u32,
+        seat_types_csv: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("update-class-seat-types.unauthorized_operation");
+        }
+
+        let allowed_seat_types = if seat_types_csv == "any" {
+            None
+        } else {
+            let mut seat_types = vec![];
+            for token in seat_types_csv.split(',') {
+                let mut chars = token.trim().chars();
+                match (chars.next().and_then(SeatType::from_char), chars.next()) {
+                    (Some(seat_type), None) if !seat_types.contains(&seat_type) => {
+                        seat_types.push(seat_type)
+                    }
+                    _ => return self.msg("update-class-seat-types.invalid_seat_types"),
+                }
+            }
+            Some(seat_types)
+        };
+
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("update-class-seat-types.flight_not_found"),
+        };
+        let seat_class = match flight.seat_classes.get_mut((class_index as usize).wrapping_sub(1)) {
+            Some(seat_class) => seat_class,
+            None => return self.msg("update-class-seat-types.invalid_class"),
+        };
+
+        let description = match &allowed_seat_types {
+            Some(seat_types) => seat_types
+                .iter()
+                .map(|seat_type| seat_type.as_char().to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => "any".to_string(),
+        };
+        seat_class.allowed_seat_types = allowed_seat_types;
+        self.msg_fmt(
+            "update-class-seat-types.success",
+            &[&flight_id, &class_index, &description],
+        )
+    }
+
+    fn process_add_insurance(
+        &mut self,
+        user_id: &str,
+        reservation_id: u32,
+        coverage_type: &str,
+    ) -> String {
+        let coverage_type = match CoverageType::parse(coverage_type) {
+            Some(coverage_type) => coverage_type,
+            None => return self.msg("add-insurance.invalid_coverage_type"),
+        };
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("add-insurance.reservation_not_found"),
+        };
+        if reservation.user_id != user_id {
+            return self.msg("add-insurance.unauthorized_operation");
+        }
+        if self
+            .insurance_policies
+            .values()
+            .any(|policy| policy.reservation_id == reservation_id)
+        {
+            return self.msg("add-insurance.already_insured");
+        }
+
+        let premium = reservation.total_price * coverage_type.premium_percent() / 100;
+        let policy_id = self.next_policy_id;
+        self.insurance_policies.insert(
+            policy_id,
+            InsurancePolicy {
+                policy_id,
+                reservation_id,
+                coverage_type,
+                premium,
+                is_claimed: false,
+            },
+        );
+        self.next_policy_id += 1;
+
+        self.msg_fmt("add-insurance.success", &[&policy_id, &premium])
+    }
+
+    fn process_claim_insurance(&mut self, user_id: &str, policy_id: u32, reason: &str) -> String {
+        if reason.trim().is_empty() {
+            return self.msg("claim-insurance.reason_required");
+        }
+        let policy = match self.insurance_policies.get(&policy_id) {
+            Some(policy) => policy,
+            None => return self.msg("claim-insurance.policy_not_found"),
+        };
+        let reservation = &self.reservations[&policy.reservation_id];
+        if reservation.user_id != user_id {
+            return self.msg("claim-insurance.unauthorized_operation");
+        }
+        if policy.is_claimed {
+            return self.msg("claim-insurance.already_claimed");
+        }
+        if policy.coverage_type == CoverageType::CancellationCover && !reservation.is_cancelled {
+            return self.msg("claim-insurance.reservation_not_cancelled");
+        }
+
+        let reservation_id = reservation.reservation_id;
+        let amount = policy.coverage_type.coverage_amount(self.refundable_amount(reservation));
+        self.insurance_policies.get_mut(&policy_id).unwrap().is_claimed = true;
+        let voucher_id = self.issue_voucher(user_id, reservation_id, amount);
+
+        self.msg_fmt("claim-insurance.success", &[&voucher_id, &amount])
+    }
+
+    fn process_set_seat_preference(&mut self, user_id: &str, seat_types_csv: &str) -> String {
+        let mut preferred_seat_types = vec![];
+        if seat_types_csv != "none" {
+            for token in seat_types_csv.split(',') {
+                let mut chars = token.trim().chars();
+                match (chars.next().and_then(SeatType::from_char), chars.next()) {
+                    (Some(seat_type), None) if !preferred_seat_types.contains(&seat_type) => {
+                        preferred_seat_types.push(seat_type)
+                    }
+                    _ => return self.msg("set-seat-preference.invalid_seat_types"),
+                }
+            }
+        }
+
+        self.user_profiles
+            .entry(user_id.to_string())
+            .or_default()
+            .preferred_seat_types = preferred_seat_types;
+        self.msg("set-seat-preference.success")
+    }
+
+    fn preference_satisfaction(&self, flight_id: u32, date: &str) -> Option<f32> {
+        let mut matched = 0;
+        let mut total = 0;
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled
+                || reservation.flight_id != flight_id
+                || reservation.date != date
+            {
+                continue;
+            }
+            let preferred_seat_types = match &reservation.preferred_seat_types {
+                Some(seat_types) => seat_types,
+                None => continue,
+            };
+            total += 1;
+            if reservation
+                .seat_id
+                .chars()
+                .last()
+                .and_then(SeatType::from_char)
+                .is_some_and(|seat_type| preferred_seat_types.contains(&seat_type))
+            {
+                matched += 1;
+            }
+        }
+        if total == 0 {
+            None
+        } else {
+            Some(matched as f32 / total as f32)
+        }
+    }
+
+    fn process_satisfaction_report(
+        &self,
+        flight_id: u32,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("satisfaction-report.flight_not_found"),
+        };
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("satisfaction-report.invalid_date"),
+        };
+        if from > to {
+            return self.msg("satisfaction-report.invalid_range");
+        }
+        if (to - from).num_days() >= 366 {
+            return self.msg("satisfaction-report.range_too_long");
+        }
+
+        let mut result = vec![format!("satisfaction-report: flight {}", flight_id)];
+        let mut date = from;
+        while date <= to {
+            let date_str = date.format("%Y/%m/%d").to_string();
+            if flight.operates_on(&date_str) {
+                match self.preference_satisfaction(flight_id, &date_str) {
+                    Some(score) => result.push(format!("{} {:.2}", date_str, score)),
+                    None => result.push(format!("{} n/a", date_str)),
+                }
+            }
+            date += Duration::days(1);
+        }
+        result.join("\n")
+    }
+
+    fn manifest_csv(&self, flight: &Flight, date: &str) -> Vec<String> {
+        let mut reservations: Vec<&Reservation> = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.flight_id == flight.flight_id
+                    && reservation.date == date
+            })
+            .collect();
+        reservations.sort_by_key(|reservation| reservation.reservation_id);
+
+        let mut rows = vec!["reservation_id,user_id,seat_id,class,price,checked_in".to_string()];
+        for reservation in reservations {
+            let (seat_class, _) = flight.get_seat_class(&reservation.seat_id).unwrap();
+            rows.push(format!(
+                "{},{},{},{},{},{}",
+                reservation.reservation_id,
+                reservation.user_id,
+                reservation.seat_id,
+                seat_class,
+                reservation.total_price,
+                reservation.is_checked_in
+            ));
+        }
+        rows
+    }
+
+    fn analytics_reservations(
+        &self,
+        date_from: &str,
+        date_to: &str,
+    ) -> Result<Vec<&Reservation>, &'static str> {
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err("invalid_date"),
+        };
+        if from > to {
+            return Err("invalid_range");
+        }
+        if (to - from).num_days() >= 366 {
+            return Err("range_too_long");
+        }
+
+        let mut reservations: Vec<&Reservation> = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                self.parse_date(&reservation.date)
+                    .is_some_and(|date| from <= date && date <= to)
+            })
+            .collect();
+        reservations.sort_by_key(|reservation| reservation.reservation_id);
+        Ok(reservations)
+    }
+
+    fn seat_class_index_of(&self, reservation: &Reservation) -> Option<u32> {
+        self.flights
+            .get(&reservation.flight_id)
+            .and_then(|flight| flight.get_seat_class(&reservation.seat_id))
+            .map(|(class_index, _)| class_index)
+    }
+
+    fn process_export_analytics(
+        &self,
+        admin_token: &str,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("export-analytics.unauthorized_operation");
+        }
+        let reservations = match self.analytics_reservations(date_from, date_to) {
+            Ok(reservations) => reservations,
+            Err(reason) => return self.msg(&format!("export-analytics.{}", reason)),
+        };
+
+        let mut rows = vec![ANALYTICS_CSV_HEADER.to_string()];
+        let mut total_revenue: u64 = 0;
+        for reservation in &reservations {
+            if !reservation.is_cancelled {
+                total_revenue += reservation.total_price as u64;
+            }
+            rows.push(format!(
+                "{},{},{},{},{},{},{},{},{}",
+                reservation.reservation_id,
+                reservation.flight_id,
+                reservation.date,
+                self.seat_class_index_of(reservation)
+                    .map_or(String::new(), |class_index| class_index.to_string()),
+                reservation.base_price,
+                reservation.service_fee,
+                reservation.commission,
+                reservation.is_cancelled,
+                reservation.is_timeout_cancelled
+            ));
+        }
+        rows.push(format!("# total_revenue={}, {} rows", total_revenue, reservations.len()));
+        rows.join("\n")
+    }
+
+    fn process_export_analytics_json(
+        &self,
+        admin_token: &str,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("export-analytics-json.unauthorized_operation");
+        }
+        let reservations = match self.analytics_reservations(date_from, date_to) {
+            Ok(reservations) => reservations,
+            Err(reason) => return self.msg(&format!("export-analytics-json.{}", reason)),
+        };
+
+        let number = |value: u32| JsonValue::Number(value as f64);
+        let rows = reservations
+            .into_iter()
+            .map(|reservation| {
+                let entries = vec![
+                    ("reservation_id", number(reservation.reservation_id)),
+                    ("flight_id", number(reservation.flight_id)),
+                    ("date", JsonValue::String(reservation.date.clone())),
+                    (
+                        "seat_class_index",
+                        self.seat_class_index_of(reservation).map_or(JsonValue::Null, number),
+                    ),
+                    ("price", number(reservation.base_price)),
+                    ("service_fee", number(reservation.service_fee)),
+                    ("commission", number(reservation.commission)),
+                    ("is_cancelled", JsonValue::Bool(reservation.is_cancelled)),
+                    ("is_no_show", JsonValue::Bool(reservation.is_timeout_cancelled)),
+                ];
+                JsonValue::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key.to_string(), value))
+                        .collect(),
+                )
+            })
+            .collect();
+        JsonValue::Array(rows).to_string()
+    }
+
+    fn process_passengers_by_class(&self, flight_id: u32, date: &str) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("passengers-by-class.flight_not_found"),
+        };
+        if self.parse_date(date).is_none() {
+            return self.msg("passengers-by-class.invalid_date");
+        }
+
+        let mut seats: HashMap<&str, bool> = HashMap::new();
+        for reservation in self.reservations.values() {
+            if !reservation.is_cancelled
+                && reservation.flight_id == flight_id
+                && reservation.date == date
+            {
+                *seats.entry(&reservation.seat_id).or_default() |= reservation.is_checked_in;
+            }
+        }
+
+        let mut reserved = vec![0; flight.seat_classes.len()];
+        let mut checked_in = vec![0; flight.seat_classes.len()];
+        for (seat_id, is_checked_in) in seats {
+            if let Some((class_index, _)) = flight.get_seat_class(seat_id) {
+                reserved[class_index as usize - 1] += 1;
+                checked_in[class_index as usize - 1] += is_checked_in as u32;
+            }
+        }
+
+        let mut result = vec![format!("passengers-by-class: flight {} {}", flight_id, date)];
+        for i in 0..flight.seat_classes.len() {
+            result.push(self.msg_fmt(
+                "passengers-by-class.class",
+                &[&(i + 1), &reserved[i], &checked_in[i]],
+            ));
+        }
+        result.push(self.msg_fmt(
+            "passengers-by-class.total",
+            &[&reserved.iter().sum::<u32>(), &checked_in.iter().sum::<u32>()],
+        ));
+        result.join("\n")
+    }
+
+    fn process_boarding_list(&self, admin_token: &str, flight_id: u32, date: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("boarding-list.unauthorized_operation");
+        }
+        if !self.flights.contains_key(&flight_id) {
+            return self.msg("boarding-list.flight_not_found");
+        }
+        if self.parse_date(date).is_none() {
+            return self.msg("boarding-list.invalid_date");
+        }
+
+        let mut reservations: Vec<&Reservation> = self
+            .reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && reservation.flight_id == flight_id
+                    && reservation.date == date
+            })
+            .collect();
+        reservations.sort_by_key(|reservation| {
+            let (row, column) = reservation.seat_id.split_at(reservation.seat_id.len() - 1);
+            (row.parse::<u32>().unwrap_or(u32::MAX), column.to_string())
+        });
+
+        let mut result = vec![format!(
+            "boarding-list: flight {} {} {} passengers",
+            flight_id,
+            date,
+            reservations.len()
+        )];
+        for (i, reservation) in reservations.iter().enumerate() {
+            let status = if reservation.is_checked_in { "(checked-in)" } else { "(pending)" };
+            result.push(format!(
+                "{}. {} {} {}",
+                i + 1,
+                reservation.seat_id,
+                reservation.passenger_name,
+                status
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn process_signed_manifest(
+        &mut self,
+        current_datetime: &str,
+        admin_token: &str,
+        flight_id: u32,
+        date: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("signed-manifest.unauthorized_operation");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S") {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("signed-manifest.invalid_datetime"),
+        };
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("signed-manifest.flight_not_found"),
+        };
+        if self.parse_date(date).is_none() {
+            return self.msg("signed-manifest.invalid_date");
+        }
+
+        let mut document = vec![
+            format!("manifest: flight {} {}", flight_id, date),
+            format!("generated_at: {}", current_dt.format("%Y/%m/%d-%H:%M:%S")),
+        ];
+        document.extend(self.manifest_csv(flight, date));
+        let hash = fnv1a_64(&document.join("\n"));
+        document.push(format!("hash: {:016x}", hash));
+        self.manifest_timestamps
+            .insert((flight_id, date.to_string()), current_dt);
+
+        let mut result = vec![format!("signed-manifest: {} lines", document.len())];
+        result.extend(document);
+        result.join("\n")
+    }
+
+    fn process_verify_manifest(&self, manifest_with_hash: &str) -> String {
+        let lines: Vec<&str> = manifest_with_hash.lines().collect();
+        let (hash_line, body) = match lines.split_last() {
+            Some((hash_line, body)) if body.len() >= 2 => (hash_line, body),
+            _ => return self.msg("verify-manifest.tampered"),
+        };
+        let expected = format!("hash: {:016x}", fnv1a_64(&body.join("\n")));
+        if *hash_line != expected {
+            return self.msg("verify-manifest.tampered");
+        }
+
+        let header: Vec<&str> = body[0].split_whitespace().collect();
+        let generated_at = body[1]
+            .strip_prefix("generated_at: ")
+            .and_then(|dt| NaiveDateTime::parse_from_str(dt, "%Y/%m/%d-%H:%M:%S").ok());
+        let latest = match (header.as_slice(), generated_at) {
+            (["manifest:", "flight", flight_id, date], Some(generated_at)) => flight_id
+                .parse::<u32>()
+                .ok()
+                .and_then(|flight_id| self.manifest_timestamps.get(&(flight_id, date.to_string())))
+                .map(|latest| *latest == generated_at),
+            _ => None,
+        };
+        match latest {
+            Some(true) => self.msg("verify-manifest.ok"),
+            Some(false) => self.msg("verify-manifest.stale"),
+            None => self.msg("verify-manifest.tampered"),
+        }
+    }
+
+    fn process_set_commission_rate(
+        &mut self,
+        admin_token: &str,
+        agent_id: &str,
+        pct: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-commission-rate.unauthorized_operation");
+        }
+        if pct > 100 {
+            return self.msg("set-commission-rate.invalid_rate");
+        }
+
+        self.commission_rates.insert(agent_id.to_string(), pct);
+        self.msg_fmt("set-commission-rate.success", &[&agent_id, &pct])
+    }
+
+    fn process_agent_commission_report(
+        &self,
+        admin_token: &str,
+        agent_id: &str,
+        date_from: &str,
+        date_to: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("agent-commission.unauthorized_operation");
+        }
+        let (from, to) = match (self.parse_date(date_from), self.parse_date(date_to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.msg("agent-commission.invalid_date"),
+        };
+        if from > to {
+            return self.msg("agent-commission.invalid_range");
+        }
+
+        let mut count = 0;
+        let mut total: u64 = 0;
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled || reservation.agent_id.as_deref() != Some(agent_id) {
+                continue;
+            }
+            if self
+                .parse_date(&reservation.date)
+                .is_some_and(|date| from <= date && date <= to)
+            {
+                count += 1;
+                total += reservation.commission as u64;
+            }
+        }
+
+        format!("agent-commission: {} {} reservations total={}", agent_id, count, total)
+    }
+
+    fn process_update_fuel_surcharge(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        new_surcharge: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("update-surcharge.unauthorized_operation");
+        }
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("update-surcharge.flight_not_found"),
+        };
+        if flight
+            .seat_classes
+            .iter()
+            .any(|seat_class| new_surcharge >= seat_class.price)
+        {
+            return self.msg("update-surcharge.surcharge_too_high");
+        }
+
+        flight.fuel_surcharge = new_surcharge;
+        self.clear_search_cache();
+        self.msg_fmt("update-surcharge.success", &[&flight_id, &new_surcharge])
+    }
+
+    fn process_set_utc_offset(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        offset: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-utc-offset.unauthorized_operation");
+        }
+        let (sign, hhmm) = match offset.split_at_checked(1) {
+            Some(("+", hhmm)) => (1, hhmm),
+            Some(("-", hhmm)) => (-1, hhmm),
+            _ => return self.msg("set-utc-offset.invalid_offset"),
+        };
+        let offset_minutes = match NaiveTime::parse_from_str(hhmm, "%H:%M") {
+            Ok(time) => sign * (time - NaiveTime::MIN).num_minutes() as i32,
+            Err(_) => return self.msg("set-utc-offset.invalid_offset"),
+        };
+        if !(-12 * 60..=14 * 60).contains(&offset_minutes) {
+            return self.msg("set-utc-offset.invalid_offset");
+        }
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("set-utc-offset.flight_not_found"),
+        };
+        flight.utc_offset_minutes = offset_minutes;
+        self.clear_search_cache();
+        self.msg_fmt("set-utc-offset.success", &[&flight_id, &offset])
+    }
+
+    fn ical_escape(text: &str) -> String {
+        text.replace('\\', "\\\\")
+            .replace(';', "\\;")
+            .replace(',', "\\,")
+            .replace('\n', "\\n")
+    }
+
+    fn ical_fold(line: String) -> Vec<String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut lines = vec![];
+        let mut start = 0;
+        let mut width = 75;
+        while chars.len() - start > width {
+            let chunk: String = chars[start..start + width].iter().collect();
+            lines.push(match start {
+                0 => chunk,
+                _ => format!(" {}", chunk),
+            });
+            start += width;
+            width = 74;
+        }
+        let rest: String = chars[start..].iter().collect();
+        lines.push(match start {
+            0 => rest,
+            _ => format!(" {}", rest),
+        });
+        lines
+    }
+
+    fn process_export_ical(
+        &self,
+        date: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+        flight_ids_csv: &str,
+    ) -> String {
+        if self.parse_date(date).is_none() {
+            return self.msg("export-ical.invalid_date");
+        }
+        let mut flight_ids = vec![];
+        for flight_id in flight_ids_csv.split(',') {
+            match flight_id.trim().parse::<u32>() {
+                Ok(flight_id) => flight_ids.push(flight_id),
+                Err(_) => return self.msg("export-ical.invalid_flight_ids"),
+            }
+        }
+
+        let utc = |dt: NaiveDateTime, flight: &Flight| {
+            (dt - Duration::minutes(flight.utc_offset_minutes as i64))
+                .format("%Y%m%dT%H%M%SZ")
+                .to_string()
+        };
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//flight-booking//export-ical//EN".to_string(),
+            "CALSCALE:GREGORIAN".to_string(),
+        ];
+        for flight_id in flight_ids {
+            let flight = match self.flights.get(&flight_id) {
+                Some(flight)
+                    if flight.departure_airport == departure_airport
+                        && flight.arrival_airport == arrival_airport
+                        && flight.operates_on(date) =>
+                {
+                    flight
+                }
+                _ => {
+                    lines.push(format!(
+                        "COMMENT:flight {} not found for {} {}->{}",
+                        flight_id, date, departure_airport, arrival_airport
+                    ));
+                    continue;
+                }
+            };
+            let times = self
+                .get_flight_datetime(date, flight)
+                .zip(self.get_arrival_datetime(date, flight));
+            let (departure_dt, arrival_dt) = match times {
+                Some(times) => times,
+                None => {
+                    lines.push(format!("COMMENT:flight {} has an invalid schedule", flight_id));
+                    continue;
+                }
+            };
+
+            let available = self.available_seats_per_class(date, flight);
+            let classes: Vec<String> = flight
+                .seat_classes
+                .iter()
+                .enumerate()
+                .map(|(i, seat_class)| {
+                    format!(
+                        "class {}: {} {} ({} available)",
+                        i + 1,
+                        seat_class.price + flight.fuel_surcharge,
+                        BASE_CURRENCY,
+                        available[i]
+                    )
+                })
+                .collect();
+            let description = format!(
+                "Route {} -> {}\n{}",
+                flight.departure_airport,
+                flight.arrival_airport,
+                classes.join("\n")
+            );
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!(
+                "UID:flight-{}-{}@flight-booking",
+                flight_id,
+                departure_dt.format("%Y%m%d")
+            ));
+            lines.push(format!("DTSTAMP:{}", self.clock.format("%Y%m%dT%H%M%SZ")));
+            lines.push(format!("DTSTART:{}", utc(departure_dt, flight)));
+            lines.push(format!("DTEND:{}", utc(arrival_dt, flight)));
+            lines.push(format!(
+                "SUMMARY:Flight {} {} -> {}",
+                flight_id, flight.departure_airport, flight.arrival_airport
+            ));
+            lines.extend(Self::ical_fold(format!(
+                "DESCRIPTION:{}",
+                Self::ical_escape(&description)
+            )));
+            lines.push("END:VEVENT".to_string());
+        }
+        lines.push("END:VCALENDAR".to_string());
+        lines.join("\n")
+    }
+
+    fn process_mark_exit_rows(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        rows_csv: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("mark-exit-rows.unauthorized_operation");
+        }
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("mark-exit-rows.flight_not_found"),
+        };
+
+        let last_row = flight.seat_classes.last().map_or(0, |seat_class| seat_class.column);
+        let mut exit_rows = HashSet::new();
+        if rows_csv != "none" {
+            for row in rows_csv.split(',') {
+                match row.trim().parse::<u32>() {
+                    Ok(row) if (1..=last_row).contains(&row) => {
+                        exit_rows.insert(row);
+                    }
+                    _ => return self.msg("mark-exit-rows.invalid_rows"),
+                }
+            }
+        }
+
+        let mut rows: Vec<u32> = exit_rows.iter().copied().collect();
+        rows.sort();
+        flight.exit_rows = exit_rows;
+        let rows = match rows.is_empty() {
+            true => "none".to_string(),
+            false => rows.iter().map(u32::to_string).collect::<Vec<_>>().join(","),
+        };
+        self.msg_fmt("mark-exit-rows.success", &[&flight_id, &rows])
+    }
+
+    fn process_set_passenger_info(
+        &mut self,
+        user_id: &str,
+        reservation_id: u32,
+        age: u32,
+        needs_assistance: bool,
+    ) -> String {
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("set-passenger-info.reservation_not_found"),
+        };
+        if reservation.user_id != user_id {
+            return self.msg("set-passenger-info.unauthorized_operation");
+        }
+        if reservation.is_checked_in {
+            return self.msg("set-passenger-info.already_checked_in");
+        }
+        let flight = self.flights.get(&reservation.flight_id).unwrap();
+        if flight.violates_seat_policy(&reservation.seat_id, Some(age), needs_assistance) {
+            return self.msg("set-passenger-info.seat_policy_violation");
+        }
+
+        let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+        reservation.passenger_age = Some(age);
+        reservation.needs_assistance = needs_assistance;
+        self.msg("set-passenger-info.success")
+    }
+
+    fn process_bulk_price_update(
+        &mut self,
+        admin_token: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+        class_index: usize,
+        new_price: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("bulk-price-update.unauthorized_operation");
+        }
+        if new_price == 0 {
+            return self.msg("bulk-price-update.invalid_price");
+        }
+        if class_index == 0 {
+            return self.msg("bulk-price-update.invalid_class");
+        }
+
+        let mut updated = 0;
+        for flight in self.flights.values_mut() {
+            if flight.is_removed
+                || flight.departure_airport != departure_airport
+                || flight.arrival_airport != arrival_airport
+            {
+                continue;
+            }
+            if let Some(seat_class) = flight.seat_classes.get_mut(class_index - 1) {
+                seat_class.price = new_price;
+                updated += 1;
+            }
+        }
+        if updated > 0 {
+            self.clear_search_cache();
+        }
+
+        self.msg_fmt("bulk-price-update.success", &[&updated, &class_index, &new_price])
+    }
+
+    fn process_add_announcement(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        text: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("add-announcement.unauthorized_operation");
+        }
+        let text = text.trim();
+        if text.is_empty() || text.chars().count() > 160 {
+            return self.msg("add-announcement.invalid_text");
+        }
+
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("add-announcement.flight_not_found"),
+        };
+        if flight.announcements.len() >= 5 {
+            return self.msg("add-announcement.too_many_announcements");
+        }
+
+        flight.announcements.push(text.to_string());
+        let count = flight.announcements.len();
+        self.clear_search_cache();
+        format!("add-announcement: {}", count)
+    }
+
+    fn process_remove_announcement(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        index: usize,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("remove-announcement.unauthorized_operation");
+        }
+
+        let flight = match self.flights.get_mut(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("remove-announcement.flight_not_found"),
+        };
+        if index == 0 || index > flight.announcements.len() {
+            return self.msg("remove-announcement.index_out_of_range");
+        }
+
+        flight.announcements.remove(index - 1);
+        self.clear_search_cache();
+        self.msg("remove-announcement.success")
+    }
+
+    fn missed_connections(&self, itinerary_id: u32, delayed: &Flight) -> Vec<(u32, NaiveDateTime)> {
+        let mut missed = vec![];
+        let legs = self.itinerary_legs(&self.itineraries[&itinerary_id]);
+        for pair in legs.windows(2) {
+            let inbound = &self.reservations[&pair[0].1];
+            if inbound.flight_id != delayed.flight_id {
+                continue;
+            }
+            let arrival_dt = match self.get_arrival_datetime(&inbound.date, delayed) {
+                Some(dt) => dt,
+                None => continue,
+            };
+            let ready_at = arrival_dt + self.min_connection_time(delayed.arrival_airport);
+            if ready_at > pair[1].0 {
+                missed.push((pair[1].1, ready_at));
+            }
+        }
+        missed
+    }
+
+    fn find_broken_connections(&self, flight_id: u32, new_departure_time: NaiveTime) -> Vec<u32> {
+        let mut delayed = match self.flights.get(&flight_id) {
+            Some(flight) => flight.clone(),
+            None => return vec![],
+        };
+        let times = NaiveTime::parse_from_str(&delayed.departure_time, "%H:%M:%S")
+            .ok()
+            .zip(NaiveTime::parse_from_str(&delayed.arrival_time, "%H:%M:%S").ok());
+        let (departure, arrival) = match times {
+            Some(times) => times,
+            None => return vec![],
+        };
+        let (new_arrival, _) = arrival.overflowing_add_signed(new_departure_time - departure);
+        delayed.departure_time = new_departure_time.format("%H:%M:%S").to_string();
+        delayed.arrival_time = new_arrival.format("%H:%M:%S").to_string();
+
+        let mut itinerary_ids: Vec<u32> = self
+            .itineraries
+            .keys()
+            .copied()
+            .filter(|&itinerary_id| !self.missed_connections(itinerary_id, &delayed).is_empty())
+            .collect();
+        itinerary_ids.sort();
+        itinerary_ids
+    }
+
+    fn itinerary_legs(&self, itinerary: &Itinerary) -> Vec<(NaiveDateTime, u32)> {
+        let mut legs = vec![];
+        for reservation_id in &itinerary.reservation_ids {
+            let reservation = match self.reservations.get(reservation_id) {
+                Some(reservation) if !reservation.is_cancelled => reservation,
+                _ => continue,
+            };
+            let flight = self.flights.get(&reservation.flight_id).unwrap();
+            if let Some(flight_dt) = self.get_flight_datetime(&reservation.date, flight) {
+                legs.push((flight_dt, *reservation_id));
+            }
+        }
+        legs.sort();
+        legs
+    }
+
+    fn find_free_seat(
+        &self,
+        date: &str,
+        flight: &Flight,
+        preferred_class: u32,
+        lowest_class: Option<u32>,
+    ) -> Option<String> {
+        let mut candidates = vec![];
+        for row in 1..=flight.seat_classes.last().map_or(0, |seat_class| seat_class.column) {
+            for seat_type in &SeatType::variants() {
+                let seat_id = format!("{}{}", row, seat_type.as_char());
+                if !flight.seat_type_allowed(&seat_id)
+                    || self.is_seat_reserved(date, flight.flight_id, &seat_id)
+                    || self
+                        .active_auctions
+                        .contains_key(&self.seat_key(date, flight.flight_id, &seat_id))
+                {
+                    continue;
+                }
+                let (seat_class, _) = flight.get_seat_class(&seat_id)?;
+                if lowest_class.is_some_and(|lowest_class| seat_class > lowest_class) {
+                    continue;
+                }
+                candidates.push((seat_class != preferred_class, seat_id));
+            }
+        }
+        candidates
+            .into_iter()
+            .min_by_key(|(other_class, _)| *other_class)
+            .map(|(_, seat_id)| seat_id)
+    }
+
+    fn rebook_connection(&mut self, reservation_id: u32, ready_at: NaiveDateTime) -> bool {
+        let reservation = &self.reservations[&reservation_id];
+        let missed = self.flights.get(&reservation.flight_id).unwrap();
+        let (preferred_class, _) = missed.get_seat_class(&reservation.seat_id).unwrap();
+        let lowest_class = match self.rebook_protection {
+            true => reservation.original_class_index.map(|class_index| class_index as u32),
+            false => None,
+        };
+
+        let mut options = vec![];
+        for offset in 0..=1 {
+            let date = (ready_at.date() + Duration::days(offset))
+                .format("%Y/%m/%d")
+                .to_string();
+            let route_flights =
+                self.matching_flights(&date, missed.departure_airport, missed.arrival_airport);
+            for flight in route_flights {
+                if flight.flight_id == missed.flight_id && date == reservation.date {
+                    continue;
+                }
+                let flight_dt = match self.get_flight_datetime(&date, flight) {
+                    Some(flight_dt) if flight_dt >= ready_at => flight_dt,
+                    _ => continue,
+                };
+                if let Some(seat_id) =
+                    self.find_free_seat(&date, flight, preferred_class, lowest_class)
+                {
+                    options.push((flight_dt, flight.flight_id, date.clone(), seat_id));
+                }
+            }
+        }
+
+        let (_, flight_id, date, seat_id) = match options.into_iter().min() {
+            Some(option) => option,
+            None => return false,
+        };
+
+        let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+        let old_date = std::mem::replace(&mut reservation.date, date.clone());
+        let old_flight_id = std::mem::replace(&mut reservation.flight_id, flight_id);
+        let old_seat_id = std::mem::replace(&mut reservation.seat_id, seat_id.clone());
+        reservation.is_rerouted = true;
+        self.unreserve_seat(&old_date, old_flight_id, &old_seat_id);
+        self.reserve_seat(&date, flight_id, &seat_id);
+        true
+    }
+
+    fn delay_compensation_amount(delay_minutes: i64) -> u32 {
+        match delay_minutes {
+            minutes if minutes > 360 => 400,
+            minutes if minutes >= 180 => 250,
+            _ => 0,
+        }
+    }
+
+    fn rerouted_itinerary(&self, reservation_id: u32) -> bool {
+        self.itinerary_of(reservation_id).is_some_and(|itinerary_id| {
+            self.itineraries[&itinerary_id]
+                .reservation_ids
+                .iter()
+                .any(|id| self.reservations.get(id).is_some_and(|leg| leg.is_rerouted))
+        })
+    }
+
+    fn process_compute_delay_compensation(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        original_departure: &str,
+        actual_departure: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("delay-compensation.unauthorized_operation");
+        }
+        if !self.flights.contains_key(&flight_id) {
+            return self.msg("delay-compensation.flight_not_found");
+        }
+        let parse = |datetime| NaiveDateTime::parse_from_str(datetime, "%Y/%m/%d-%H:%M:%S");
+        let (original_dt, actual_dt) = match (parse(original_departure), parse(actual_departure)) {
+            (Ok(original_dt), Ok(actual_dt)) => (original_dt, actual_dt),
+            _ => return self.msg("delay-compensation.invalid_datetime"),
+        };
+        if actual_dt < original_dt {
+            return self.msg("delay-compensation.invalid_range");
+        }
+        let date = original_dt.format("%Y/%m/%d").to_string();
+        if self.compensated_flights.contains(&(flight_id, date.clone())) {
+            return self.msg("delay-compensation.already_compensated");
+        }
+
+        let delay_minutes = (actual_dt - original_dt).num_minutes();
+        let amount = Self::delay_compensation_amount(delay_minutes);
+        let mut passengers: Vec<(u32, String)> = vec![];
+        if amount > 0 {
+            passengers = self
+                .reservations
+                .values()
+                .filter(|reservation| {
+                    !reservation.is_cancelled
+                        && reservation.is_checked_in
+                        && !reservation.is_rerouted
+                        && reservation.flight_id == flight_id
+                        && reservation.date == date
+                        && !self.rerouted_itinerary(reservation.reservation_id)
+                })
+                .map(|reservation| (reservation.reservation_id, reservation.user_id.clone()))
+                .collect();
+            passengers.sort();
+            self.compensated_flights.insert((flight_id, date.clone()));
+        }
+        for (reservation_id, user_id) in &passengers {
+            self.issue_voucher(user_id, *reservation_id, amount);
+        }
+        let total = amount * passengers.len() as u32;
+        self.record_audit(
+            "admin",
+            "delay-compensation",
+            format!(
+                "flight_id={} date={} delay_minutes={} total={}",
+                flight_id, date, delay_minutes, total
+            ),
+        );
+        self.msg_fmt(
+            "delay-compensation.success",
+            &[&flight_id, &delay_minutes, &passengers.len(), &total],
+        )
+    }
+
+    fn is_peak_date(&self, reservation: &Reservation) -> bool {
+        NaiveDate::parse_from_str(&reservation.date, "%Y/%m/%d")
+            .is_ok_and(|date| self.peak_dates.contains(&date))
+    }
+
+    fn swap_crosses_class_on_peak_date(&self, a: &Reservation, b: &Reservation) -> bool {
+        let flight = &self.flights[&a.flight_id];
+        let class_of = |seat_id: &str| flight.get_seat_class(seat_id).map(|(class, _)| class);
+        self.is_peak_date(a) && class_of(&a.seat_id) != class_of(&b.seat_id)
+    }
+
+    fn process_set_peak_dates(&mut self, admin_token: &str, dates_csv: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-peak-dates.unauthorized_operation");
+        }
+        let mut peak_dates = HashSet::new();
+        if dates_csv != "none" {
+            for date in dates_csv.split(',') {
+                match self.parse_date(date.trim()) {
+                    Some(date) => peak_dates.insert(date),
+                    None => return self.msg("set-peak-dates.invalid_date"),
+                };
+            }
+        }
+        self.peak_dates = peak_dates;
+        self.record_audit("admin", "set-peak-dates", format!("dates={}", dates_csv));
+        self.msg_fmt("set-peak-dates.success", &[&self.peak_dates.len()])
+    }
+
+    fn process_set_rebook_protection(&mut self, admin_token: &str, enabled: bool) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-rebook-protection.unauthorized_operation");
+        }
+        self.rebook_protection = enabled;
+        self.msg_fmt("set-rebook-protection.success", &[&enabled])
+    }
+
+    fn process_force_rebook(
+        &mut self,
+        admin_token: &str,
+        reservation_id: u32,
+        target_class: u32,
+        reason: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("force-rebook.unauthorized_operation");
+        }
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return self.msg("force-rebook.reason_required");
+        }
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("force-rebook.reservation_not_found"),
+        };
+        let flight = self.flights.get(&reservation.flight_id).unwrap();
+        if target_class == 0 || target_class as usize > flight.seat_classes.len() {
+            return self.msg("force-rebook.invalid_class");
+        }
+        let current_class = flight.get_seat_class(&reservation.seat_id).map(|(class, _)| class);
+        if self.is_peak_date(reservation) && current_class != Some(target_class) {
+            return self.msg("force-rebook.peak_date");
+        }
+        let seat_id = match self.find_free_seat(&reservation.date, flight, target_class, None) {
+            Some(seat_id)
+                if flight.get_seat_class(&seat_id).map(|(class, _)| class) == Some(target_class) =>
+            {
+                seat_id
+            }
+            _ => return self.msg("force-rebook.no_seat_available"),
+        };
+
+        let date = reservation.date.clone();
+        let flight_id = reservation.flight_id;
+        let old_seat_id = reservation.seat_id.clone();
+        self.reservations.get_mut(&reservation_id).unwrap().seat_id = seat_id.clone();
+        self.unreserve_seat(&date, flight_id, &old_seat_id);
+        self.reserve_seat(&date, flight_id, &seat_id);
+        self.record_audit(
+            "admin",
+            "force-rebook",
+            format!(
+                "reservation={} from={} to={} reason={}",
+                reservation_id, old_seat_id, seat_id, reason
+            ),
+        );
+
+        self.msg_fmt("force-rebook.success", &[&reservation_id, &seat_id])
+    }
+
+    fn process_propagate_delay(
+        &mut self,
+        admin_token: &str,
+        flight_id: u32,
+        delay_hours: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("propagate-delay.unauthorized_operation");
+        }
+
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("propagate-delay.flight_not_found"),
+        };
+
+        let delay = Duration::hours(delay_hours as i64);
+        let times = NaiveTime::parse_from_str(&flight.departure_time, "%H:%M:%S")
+            .ok()
+            .zip(NaiveTime::parse_from_str(&flight.arrival_time, "%H:%M:%S").ok());
+        let (departure, arrival) = match times {
+            Some(times) => times,
+            None => return self.msg("propagate-delay.invalid_flight_datetime"),
+        };
+        let (new_departure, departure_wrap) = departure.overflowing_add_signed(delay);
+        if departure_wrap != 0 {
+            return self.msg("propagate-delay.delay_crosses_midnight");
+        }
+        let (new_arrival, _) = arrival.overflowing_add_signed(delay);
+        let broken_itineraries = self.find_broken_connections(flight_id, new_departure);
+
+        let flight = self.flights.get_mut(&flight_id).unwrap();
+        flight.departure_time = new_departure.format("%H:%M:%S").to_string();
+        flight.arrival_time = new_arrival.format("%H:%M:%S").to_string();
+        let flight = flight.clone();
+        self.clear_search_cache();
+        self.hooks
+            .iter()
+            .for_each(|h| h.on_flight_delayed(flight_id, &flight.departure_time));
+
+        let broken: Vec<(u32, NaiveDateTime)> = broken_itineraries
+            .into_iter()
+            .flat_map(|itinerary_id| self.missed_connections(itinerary_id, &flight))
+            .collect();
+
+        let mut rebooked = 0;
+        let mut manual = 0;
+        for (reservation_id, ready_at) in broken {
+            if self.rebook_connection(reservation_id, ready_at) {
+                rebooked += 1;
+            } else {
+                manual += 1;
+            }
+        }
+
+        self.msg_fmt(
+            "propagate-delay.success",
+            &[&flight_id, &delay_hours, &rebooked, &manual],
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_generate_promo_codes(
+        &mut self,
+        admin_token: &str,
+        prefix: &str,
+        count: u32,
+        discount_type: &str,
+        value: u32,
+        max_uses: u32,
+        expiry_date: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("generate-promos.unauthorized_operation");
+        }
+
+        let discount = match discount_type {
+            "percent" if value <= 100 => PromoDiscount::Percent(value),
+            "fixed" => PromoDiscount::Fixed(value),
+            _ => return self.msg("generate-promos.invalid_discount"),
+        };
+        let expiry_date = match self.parse_date(expiry_date) {
+            Some(date) => date,
+            None => return self.msg("generate-promos.invalid_date"),
+        };
+        if count == 0 || count > 1000 || max_uses == 0 {
+            return self.msg("generate-promos.invalid_count");
+        }
+
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        let mut codes = vec![];
+        while codes.len() < count as usize {
+            let suffix: String = (0..6)
+                .map(|_| ALPHABET[self.rng.next_below(ALPHABET.len() as u64) as usize] as char)
+                .collect();
+            let code = format!("{}-{}", prefix, suffix);
+            if self.promo_codes.contains_key(&code) {
+                continue;
+            }
+            self.promo_codes.insert(
+                code.clone(),
+                PromoCode {
+                    discount,
+                    max_uses,
+                    uses: 0,
+                    expiry_date,
+                },
+            );
+            codes.push(code);
+        }
+
+        let mut result = vec![self.msg_fmt("generate-promos.generated", &[&codes.len()])];
+        result.extend(codes);
+        result.join("\n")
+    }
+
+    fn process_has_flown(
+        &self,
+        current_datetime: &str,
+        user_id: &str,
+        departure_airport: u32,
+        arrival_airport: u32,
+    ) -> String {
+        let current_date = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt.date(),
+            Err(_) => return self.msg("has-flown.invalid_datetime"),
+        };
+
+        let mut flown_dates: Vec<NaiveDate> = self
+            .reservations
+            .values()
+            .filter(|reservation| reservation.user_id == user_id && !reservation.is_cancelled)
+            .filter(|reservation| {
+                self.flights.get(&reservation.flight_id).is_some_and(|flight| {
+                    flight.departure_airport == departure_airport
+                        && flight.arrival_airport == arrival_airport
+                })
+            })
+            .filter_map(|reservation| self.parse_date(&reservation.date))
+            .filter(|&date| date < current_date)
+            .collect();
+        flown_dates.sort();
+
+        match flown_dates.first() {
+            Some(first_date) => self.msg_fmt(
+                "has-flown.yes",
+                &[&first_date.format("%Y/%m/%d"), &flown_dates.len()],
+            ),
+            None => self.msg("has-flown.no"),
+        }
+    }
+
+    fn process_name_change(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+        new_passenger_name: &str,
+    ) -> String {
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("name-change.reservation_not_found"),
+        };
+        if reservation.user_id != user_id {
+            return self.msg("name-change.unauthorized_operation");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("name-change.invalid_datetime"),
+        };
+        let flight = &self.flights[&reservation.flight_id];
+        let flight_dt = match self.get_flight_datetime(&reservation.date, flight) {
+            Some(dt) => dt,
+            None => return self.msg("name-change.invalid_flight_datetime"),
+        };
+        if current_dt >= flight_dt {
+            return self.msg("name-change.already_departed");
+        }
+        if reservation.name_change_used {
+            return self.msg("name-change.already_used");
+        }
+        if new_passenger_name.is_empty()
+            || new_passenger_name.chars().count() > MAX_PASSENGER_NAME_CHARS
+        {
+            return self.msg("name-change.invalid_name");
+        }
+
+        let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+        let old_passenger_name =
+            std::mem::replace(&mut reservation.passenger_name, new_passenger_name.to_string());
+        reservation.name_change_used = true;
+        self.record_audit(
+            user_id,
+            "name-change",
+            format!(
+                "reservation_id={} old_name={} new_name={}",
+                reservation_id, old_passenger_name, new_passenger_name
+            ),
+        );
+        self.msg_fmt("name-change.success", &[&reservation_id, &new_passenger_name])
+    }
+
+    fn process_create_swap_request(
+        &mut self,
+        current_datetime: &str,
+        initiator_user_id: &str,
+        initiator_reservation_id: u32,
+        target_user_id: &str,
+        target_reservation_id: u32,
+    ) -> String {
+        let (initiator, current_dt, flight_dt) = match self.check_cancellable(
+            current_datetime,
+            initiator_user_id,
+            initiator_reservation_id,
+        ) {
+            Ok(checked) => checked,
+            Err(reason) => return self.msg(&format!("create-swap.{}", reason)),
+        };
+        let target = match self.reservations.get(&target_reservation_id) {
+            Some(target) if !target.is_cancelled && target.user_id == target_user_id => target,
+            _ => return self.msg("create-swap.target_reservation_not_found"),
+        };
+        if target_user_id == initiator_user_id
+            || target.flight_id != initiator.flight_id
+            || target.date != initiator.date
+        {
+            return self.msg("create-swap.not_swappable");
+        }
+        if initiator.is_checked_in || target.is_checked_in {
+            return self.msg("create-swap.already_checked_in");
+        }
+        if self.swap_crosses_class_on_peak_date(initiator, target) {
+            return self.msg("create-swap.peak_date");
+        }
+
+        let request_id = self.swap_requests.len() as u32 + 1;
+        let expires_at = (current_dt + Duration::hours(SWAP_REQUEST_HOURS))
+            .min(flight_dt - Duration::hours(2));
+        self.swap_requests.insert(
+            request_id,
+            SwapRequest {
+                request_id,
+                initiator_user_id: initiator_user_id.to_string(),
+                initiator_reservation_id,
+                target_user_id: target_user_id.to_string(),
+                target_reservation_id,
+                expires_at,
+                status: SwapStatus::Pending,
+            },
+        );
+
+        self.msg_fmt("create-swap.success", &[&request_id, &target_user_id])
+    }
+
+    fn process_respond_swap(
+        &mut self,
+        current_datetime: &str,
+        target_user_id: &str,
+        request_id: u32,
+        accept: bool,
+    ) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("respond-swap.invalid_datetime"),
+        };
+        let request = match self.swap_requests.get_mut(&request_id) {
+            Some(request) if request.status == SwapStatus::Pending => request,
+            _ => return self.msg("respond-swap.request_not_found"),
+        };
+        if request.target_user_id != target_user_id {
+            return self.msg("respond-swap.unauthorized_operation");
+        }
+        if request.expires_at <= current_dt {
+            request.status = SwapStatus::Expired;
+            return self.msg("respond-swap.request_expired");
+        }
+        if !accept {
+            request.status = SwapStatus::Rejected;
+            return self.msg_fmt("respond-swap.rejected", &[&request_id]);
+        }
+
+        let initiator_id = request.initiator_reservation_id;
+        let target_id = request.target_reservation_id;
+        let initiator_user_id = request.initiator_user_id.clone();
+        if let (Some(initiator), Some(target)) =
+            (self.reservations.get(&initiator_id), self.reservations.get(&target_id))
+        {
+            if self.swap_crosses_class_on_peak_date(initiator, target) {
+                return self.msg("respond-swap.peak_date");
+            }
+        }
+        if !self.process_seat_swap(&initiator_user_id, initiator_id, target_user_id, target_id) {
+            return self.msg("respond-swap.swap_failed");
+        }
+        self.swap_requests.get_mut(&request_id).unwrap().status = SwapStatus::Accepted;
+        self.msg_fmt("respond-swap.accepted", &[&request_id])
+    }
+
+    fn process_seat_swap(
+        &mut self,
+        user_a: &str,
+        reservation_a: u32,
+        user_b: &str,
+        reservation_b: u32,
+    ) -> bool {
+        let swappable = |reservation: Option<&Reservation>, user_id: &str| {
+            reservation.is_some_and(|reservation| {
+                reservation.user_id == user_id
+                    && !reservation.is_cancelled
+                    && !reservation.is_checked_in
+            })
+        };
+        if !swappable(self.reservations.get(&reservation_a), user_a)
+            || !swappable(self.reservations.get(&reservation_b), user_b)
+        {
+            return false;
+        }
+
+        let seat_a = self.reservations[&reservation_a].seat_id.clone();
+        let seat_b = self.reservations[&reservation_b].seat_id.clone();
+        self.reservations.get_mut(&reservation_a).unwrap().seat_id = seat_b;
+        self.reservations.get_mut(&reservation_b).unwrap().seat_id = seat_a;
+        true
+    }
+
+    fn process_purge_expired_locks(&mut self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("purge-expired-locks.invalid_datetime"),
+        };
+
+        let mut expired_locks: Vec<u64> = self
+            .seat_locks
+            .iter()
+            .filter(|(_, lock)| lock.expires_at <= current_dt)
+            .map(|(&lock_id, _)| lock_id)
+            .collect();
+        expired_locks.sort();
+        for lock_id in &expired_locks {
+            let lock = self.seat_locks.remove(lock_id).unwrap();
+            if self.seat_reservations
+                .get(&lock.date)
+                .and_then(|flights| flights.get(&lock.flight_id))
+                .and_then(|seats| seats.get(&lock.seat_id))
+                == Some(&SeatState::TentativeLock(*lock_id))
+            {
+                self.set_seat_state(&lock.date, lock.flight_id, &lock.seat_id, SeatState::Free);
+            }
+            if self.lock_expiry_fee > 0 {
+                self.debit_log.push(DebitEntry {
+                    user_id: lock.user_id,
+                    amount: self.lock_expiry_fee,
+                    reason: "expired lock fee".to_string(),
+                    date: lock.expires_at.date(),
+                    is_settled: false,
+                });
+            }
+        }
+
+        let mut expired_swaps = 0;
+        for request in self.swap_requests.values_mut() {
+            if request.status == SwapStatus::Pending && request.expires_at <= current_dt {
+                request.status = SwapStatus::Expired;
+                expired_swaps += 1;
+            }
+        }
+
+        self.msg_fmt("purge-expired-locks.success", &[&expired_locks.len(), &expired_swaps])
+    }
+
+    fn process_set_lock_expiry_fee(&mut self, admin_token: &str, fee: u32) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-lock-expiry-fee.unauthorized_operation");
+        }
+        self.lock_expiry_fee = fee;
+        self.record_audit("admin", "set-lock-expiry-fee", format!("fee={}", fee));
+        self.msg_fmt("set-lock-expiry-fee.success", &[&fee])
+    }
+
+    fn outstanding_debits(&self, user_id: &str) -> u32 {
+        self.debit_log
+            .iter()
+            .filter(|entry| entry.user_id == user_id && !entry.is_settled)
+            .map(|entry| entry.amount)
+            .sum()
+    }
+
+    fn process_get_debit_log(&self, user_id: &str) -> String {
+        let entries: Vec<&DebitEntry> =
+            self.debit_log.iter().filter(|entry| entry.user_id == user_id).collect();
+        let mut result = vec![format!(
+            "debit-log: {} {} entries outstanding={}",
+            user_id,
+            entries.len(),
+            self.outstanding_debits(user_id)
+        )];
+        for entry in entries {
+            result.push(format!(
+                "{} {} {} {}",
+                entry.date.format("%Y/%m/%d"),
+                entry.amount,
+                entry.reason,
+                if entry.is_settled { "settled" } else { "outstanding" }
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn process_lock_seat(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        date: &str,
+        flight_id: u32,
+        seat_id: &str,
+    ) -> String {
+        if self.blocked_users.contains(user_id) {
+            return self.msg("lock-seat.user_blocked");
+        }
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("lock-seat.flight_not_found"),
+        };
+        if !flight.operates_on(date) {
+            return self.msg("lock-seat.flight_not_operating_on_date");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("lock-seat.invalid_datetime"),
+        };
+        let flight_dt = match self.get_flight_datetime(date, flight) {
+            Some(dt) => dt,
+            None => return self.msg("lock-seat.invalid_flight_datetime"),
+        };
+        if self.is_too_late(current_dt, flight_dt) {
+            return self.msg("lock-seat.too_late");
+        }
+        if flight.get_seat_class(seat_id).is_none() || !flight.seat_type_allowed(seat_id) {
+            return self.msg("lock-seat.invalid_seat_id");
+        }
+        if self.is_seat_reserved(date, flight_id, seat_id)
+            || self
+                .active_auctions
+                .contains_key(&self.seat_key(date, flight_id, seat_id))
+        {
+            return self.msg("lock-seat.seat_unavailable");
+        }
+
+        let lock_id = self.next_lock_id;
+        self.next_lock_id += 1;
+        let expires_at = current_dt + Duration::minutes(SEAT_LOCK_MINUTES);
+        self.seat_locks.insert(
+            lock_id,
+            SeatLock {
+                user_id: user_id.to_string(),
+                date: date.to_string(),
+                flight_id,
+                seat_id: seat_id.to_string(),
+                expires_at,
+            },
+        );
+        self.set_seat_state(date, flight_id, seat_id, SeatState::TentativeLock(lock_id));
+
+        self.msg_fmt(
+            "lock-seat.success",
+            &[&lock_id, &expires_at.format("%Y/%m/%d-%H:%M:%S")],
+        )
+    }
+
+    fn owned_lock(
+        &self,
+        current_datetime: &str,
+        user_id: &str,
+        lock_id: u64,
+    ) -> Result<&SeatLock, &'static str> {
+        let current_dt = NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S")
+            .map_err(|_| "invalid_datetime")?;
+        let lock = self.seat_locks.get(&lock_id).ok_or("lock_not_found")?;
+        if lock.user_id != user_id {
+            return Err("unauthorized_operation");
+        }
+        if lock.expires_at <= current_dt {
+            return Err("lock_expired");
+        }
+        Ok(lock)
+    }
+
+    fn process_transfer_lock(
+        &mut self,
+        current_datetime: &str,
+        from_user_id: &str,
+        lock_id: u64,
+        to_user_id: &str,
+    ) -> String {
+        let lock = match self.owned_lock(current_datetime, from_user_id, lock_id) {
+            Ok(lock) => lock,
+            Err(reason) => return self.msg(&format!("transfer-lock.{}", reason)),
+        };
+        if self.blocked_users.contains(to_user_id) {
+            return self.msg("transfer-lock.user_blocked");
+        }
+        let already_held = self.seat_locks.iter().any(|(&other_id, other)| {
+            other_id != lock_id
+                && other.user_id == to_user_id
+                && other.date == lock.date
+                && other.flight_id == lock.flight_id
+                && other.seat_id == lock.seat_id
+                && self.seat_state(&other.date, other.flight_id, &other.seat_id)
+                    == SeatState::TentativeLock(other_id)
+        });
+        if to_user_id == from_user_id || already_held {
+            return self.msg("transfer-lock.already_locked");
+        }
+
+        self.seat_locks.get_mut(&lock_id).unwrap().user_id = to_user_id.to_string();
+        self.msg_fmt("transfer-lock.success", &[&lock_id, &to_user_id])
+    }
+
+    fn process_confirm_lock(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        lock_id: u64,
+    ) -> String {
+        let lock = match self.owned_lock(current_datetime, user_id, lock_id) {
+            Ok(lock) => lock,
+            Err(reason) => return self.msg(&format!("confirm-lock.{}", reason)),
+        };
+        let date = lock.date.clone();
+        let flight_id = lock.flight_id;
+        let seat_id = lock.seat_id.clone();
+
+        self.set_seat_state(&date, flight_id, &seat_id, SeatState::Free);
+        let next_reservation_id = self.next_reservation_id;
+        let result = self.process_reserve(current_datetime, user_id, &date, flight_id, &seat_id);
+        if self.next_reservation_id == next_reservation_id {
+            self.set_seat_state(&date, flight_id, &seat_id, SeatState::TentativeLock(lock_id));
+        } else {
+            self.seat_locks.remove(&lock_id);
+        }
+        result
+    }
+
+    fn process_submit_review(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        reservation_id: u32,
+        stars: u8,
+        comment: &str,
+    ) -> String {
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("submit-review.reservation_not_found"),
+        };
+        if reservation.user_id != user_id {
+            return self.msg("submit-review.unauthorized_operation");
+        }
+        if !(1..=5).contains(&stars) {
+            return self.msg("submit-review.invalid_stars");
+        }
+        if comment.chars().count() > MAX_REVIEW_COMMENT_CHARS {
+            return self.msg("submit-review.comment_too_long");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("submit-review.invalid_datetime"),
+        };
+        let flight = self.flights.get(&reservation.flight_id).unwrap();
+        match self.get_arrival_datetime(&reservation.date, flight) {
+            Some(arrival_dt) if current_dt > arrival_dt => {}
+            Some(_) => return self.msg("submit-review.flight_not_arrived"),
+            None => return self.msg("submit-review.invalid_flight_datetime"),
+        }
+        if self
+            .reviews
+            .iter()
+            .any(|review| review.reservation_id == reservation_id)
+        {
+            return self.msg("submit-review.already_reviewed");
+        }
+
+        let review_id = self.reviews.len() as u32 + 1;
+        self.reviews.push(FlightReview {
+            review_id,
+            reservation_id,
+            user_id: user_id.to_string(),
+            flight_id: reservation.flight_id,
+            date: reservation.date.clone(),
+            stars,
+            comment: comment.to_string(),
+        });
+
+        self.msg_fmt("submit-review.success", &[&review_id])
+    }
+
+    fn process_get_reviews(&self, flight_id: u32) -> String {
+        if !self.flights.contains_key(&flight_id) {
+            return self.msg("get-reviews.flight_not_found");
+        }
+        let reviews: Vec<&FlightReview> = self
+            .reviews
+            .iter()
+            .filter(|review| review.flight_id == flight_id)
+            .collect();
+        let average = match reviews.len() {
+            0 => 0.0,
+            count => {
+                reviews.iter().map(|review| review.stars as f32).sum::<f32>() / count as f32
+            }
+        };
+
+        let mut result = vec![format!("get-reviews: {} {:.1}", reviews.len(), average)];
+        for review in reviews {
+            result.push(format!(
+                "{} {} {} {} {}",
+                review.review_id, review.user_id, review.date, review.stars, review.comment
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn process_set_first_flyer_discount(&mut self, admin_token: &str, pct: u32) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-first-flyer-discount.unauthorized_operation");
+        }
+        if pct > 100 {
+            return self.msg("set-first-flyer-discount.invalid_percent");
+        }
+        self.first_flyer_discount_pct = pct;
+        self.msg_fmt("set-first-flyer-discount.success", &[&pct])
+    }
+
+    fn process_set_service_fee(&mut self, admin_token: &str, fee: u32) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-service-fee.unauthorized_operation");
+        }
+        if fee > MAX_SERVICE_FEE {
+            return self.msg("set-service-fee.invalid_fee");
+        }
+        self.service_fee = fee;
+        self.msg_fmt("set-service-fee.success", &[&fee])
+    }
+
+    fn process_set_fee_refundable(&mut self, admin_token: &str, refundable: bool) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-fee-refundable.unauthorized_operation");
+        }
+        self.fee_refundable = refundable;
+        self.msg_fmt("set-fee-refundable.success", &[&refundable])
+    }
+
+    fn process_start_kiosk_session(&mut self, user_id: &str, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("start-kiosk-session.invalid_datetime"),
+        };
+        if self.blocked_users.contains(user_id) {
+            return self.msg("start-kiosk-session.user_blocked");
+        }
+        self.clock = self.clock.max(current_dt);
+        self.active_kiosk_sessions
+            .retain(|_, session| session.expires_at > current_dt);
+
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        let session_id = self.next_kiosk_session_id;
+        let suffix: String = (0..8)
+            .map(|_| ALPHABET[self.rng.next_below(ALPHABET.len() as u64) as usize] as char)
+            .collect();
+        let token = format!("K{}-{}", session_id, suffix);
+        let expires_at = current_dt + Duration::minutes(KIOSK_SESSION_MINUTES);
+        self.active_kiosk_sessions.insert(
+            session_id,
+            KioskSession {
+                token: token.clone(),
+                user_id: user_id.to_string(),
+                started_at: current_dt,
+                expires_at,
+            },
+        );
+        self.next_kiosk_session_id += 1;
+
+        self.msg_fmt(
+            "start-kiosk-session.success",
+            &[&token, &expires_at.format("%Y/%m/%d-%H:%M:%S")],
+        )
+    }
+
+    fn kiosk_user(&self, command: &str, token: &str) -> Result<String, String> {
+        let session = token
+            .strip_prefix('K')
+            .and_then(|rest| rest.split_once('-'))
+            .and_then(|(session_id, _)| session_id.parse::<u32>().ok())
+            .and_then(|session_id| self.active_kiosk_sessions.get(&session_id))
+            .filter(|session| session.token == token);
+        match session {
+            Some(session)
+                if session.started_at <= self.clock && self.clock < session.expires_at =>
+            {
+                Ok(session.user_id.clone())
+            }
+            Some(_) => Err(self.msg(&format!("{}.session_expired", command))),
+            None => Err(self.msg(&format!("{}.invalid_session", command))),
+        }
+    }
+
+    fn checkin_reservation_id(&self, user_id: &str, flight_id: u32, date: &str) -> Option<u32> {
+        self.reservations
+            .values()
+            .filter(|reservation| {
+                !reservation.is_cancelled
+                    && !reservation.is_checked_in
+                    && reservation.user_id == user_id
+                    && reservation.flight_id == flight_id
+                    && reservation.date == date
+            })
+            .map(|reservation| reservation.reservation_id)
+            .min()
+    }
+
+    fn process_join_checkin_queue(
+        &mut self,
+        current_datetime: &str,
+        user_id: &str,
+        flight_id: u32,
+        date: &str,
+    ) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("join-checkin-queue.invalid_datetime"),
+        };
+        if self.checkin_reservation_id(user_id, flight_id, date).is_none() {
+            return self.msg("join-checkin-queue.reservation_not_found");
+        }
+        if self.checkin_queue.iter().any(|entry| entry.user_id == user_id) {
+            return self.msg("join-checkin-queue.already_in_queue");
+        }
+
+        let entry_id = self.next_checkin_queue_entry_id;
+        self.next_checkin_queue_entry_id += 1;
+        self.checkin_queue.push_back(CheckinQueueEntry {
+            entry_id,
+            user_id: user_id.to_string(),
+            flight_id,
+            date: date.to_string(),
+            joined_at: current_dt,
+        });
+        self.msg_fmt("join-checkin-queue.success", &[&entry_id, &self.checkin_queue.len()])
+    }
+
+    fn process_serve_checkin_queue(&mut self, current_datetime: &str, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("serve-checkin-queue.unauthorized_operation");
+        }
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("serve-checkin-queue.invalid_datetime"),
+        };
+        let entry = match self.checkin_queue.pop_front() {
+            Some(entry) => entry,
+            None => return self.msg("serve-checkin-queue.queue_empty"),
+        };
+
+        let waited = (current_dt - entry.joined_at).num_minutes().max(0);
+        let reservation_id = self.checkin_reservation_id(&entry.user_id, entry.flight_id, &entry.date);
+        let checkin = match reservation_id {
+            Some(reservation_id) => {
+                self.process_checkin(current_datetime, &entry.user_id, reservation_id)
+            }
+            None => self.msg("checkin.reservation_not_found"),
+        };
+        let served = self.msg_fmt(
+            "serve-checkin-queue.success",
+            &[&entry.entry_id, &entry.user_id, &waited],
+        );
+        format!("{}\n{}", served, checkin)
+    }
+
+    fn process_estimate_queue_wait(&self, current_datetime: &str, user_id: &str) -> String {
+        if NaiveDateTime::parse_from_str(current_datetime, "%Y/%m/%d-%H:%M:%S").is_err() {
+            return self.msg("queue-wait.invalid_datetime");
+        }
+        let position = match self
+            .checkin_queue
+            .iter()
+            .position(|entry| entry.user_id == user_id)
+        {
+            Some(index) => index + 1,
+            None => return self.msg("queue-wait.not_in_queue"),
+        };
+        let minutes = (position - 1) * CHECKIN_MINUTES_PER_PASSENGER;
+        self.msg_fmt("queue-wait.success", &[&position, &minutes])
+    }
+
+    fn process_get_failed_commands(&self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("get-failed-commands.unauthorized_operation");
+        }
+
+        let mut result = vec![format!("get-failed-commands: {}", self.failed_commands.len())];
+        for (query_index, query_line) in &self.failed_commands {
+            result.push(format!("{} {}", query_index, query_line));
+        }
+
+        result.join("\n")
+    }
+
+    fn process_batch(&mut self, queries: &[&str]) -> Vec<String> {
+        let mut lines = queries.iter().map(|query| query.to_string());
+        let mut results = vec![];
+        while let Some(line) = lines.next() {
+            results.push(self.process_query(line.trim(), &mut lines));
+        }
+        results
+    }
+
+    fn process_delete_user_data(&mut self, admin_token: &str, user_id: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("delete-user-data.unauthorized_operation");
+        }
+        if user_id == REDACTED {
+            return self.msg("delete-user-data.invalid_user");
+        }
+        self.record_audit("admin", "delete-user-data", format!("user_id={}", user_id));
+
+        let mut anonymized = 0;
+        for reservation in self.reservations.values_mut() {
+            if reservation.user_id == user_id || reservation.passenger_name == user_id {
+                if reservation.user_id == user_id {
+                    reservation.user_id = REDACTED.to_string();
+                }
+                if reservation.passenger_name == user_id {
+                    reservation.passenger_name = REDACTED.to_string();
+                }
+                anonymized += 1;
+            }
+        }
+        for voucher in self.vouchers.values_mut() {
+            if voucher.user_id == user_id {
+                voucher.user_id = REDACTED.to_string();
+                anonymized += 1;
+            }
+        }
+        for itinerary in self.itineraries.values_mut() {
+            if itinerary.user_id == user_id {
+                itinerary.user_id = REDACTED.to_string();
+                anonymized += 1;
+            }
+        }
+        for auction in self.active_auctions.values_mut() {
+            let before = auction.bids.len();
+            auction.bids.retain(|(bidder, _)| bidder != user_id);
+            anonymized += before - auction.bids.len();
+        }
+        for queue in self.waitlists.values_mut() {
+            let before = queue.len();
+            queue.retain(|entry| entry.user_id != user_id);
+            anonymized += before - queue.len();
+        }
+        for members in self.corporate_accounts.values_mut() {
+            let before = members.len();
+            members.retain(|member| member != user_id);
+            anonymized += before - members.len();
+        }
+        anonymized += self.user_profiles.remove(user_id).is_some() as usize;
+        anonymized += self.alert_prefs.remove(user_id).is_some() as usize;
+        anonymized += self.miles.remove(user_id).is_some() as usize;
+        anonymized += self.wallets.remove(user_id).is_some() as usize;
+        anonymized += self.auction_notices.remove(user_id).is_some() as usize;
+        for entry in &mut self.debit_log {
+            if entry.user_id == user_id {
+                entry.user_id = REDACTED.to_string();
+                anonymized += 1;
+            }
+        }
+
+        self.msg_fmt("delete-user-data.success", &[&anonymized])
+    }
+
+    fn process_import_reservations_csv(&mut self, admin_token: &str, csv_data: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("import-reservations.unauthorized_operation");
+        }
+
+        let mut imported = 0;
+        let mut errors = 0;
+        let mut conflicts = vec![];
+        for (i, line) in csv_data.lines().enumerate() {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let (user_id, passenger_name, date, flight_id, seat_id) = match fields[..] {
+                [user_id, passenger_name, date, flight_id, seat_id]
+                    if !user_id.is_empty() && !passenger_name.is_empty() =>
+                {
+                    match flight_id.parse::<u32>() {
+                        Ok(flight_id) => (user_id, passenger_name, date, flight_id, seat_id),
+                        Err(_) => {
+                            errors += 1;
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    errors += 1;
+                    continue;
+                }
+            };
+            let price = match self.flights.get(&flight_id) {
+                Some(flight)
+                    if flight.operates_on(date)
+                        && self.parse_date(date).is_some()
+                        && flight.seat_type_allowed(seat_id) =>
+                {
+                    match flight.get_seat_class(seat_id) {
+                        Some((_, price)) => price + flight.fuel_surcharge,
+                        None => {
+                            errors += 1;
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    errors += 1;
+                    continue;
+                }
+            };
+            if self.is_seat_reserved(date, flight_id, seat_id)
+                || self
+                    .active_auctions
+                    .contains_key(&self.seat_key(date, flight_id, seat_id))
+            {
+                conflicts.push(self.msg_fmt(
+                    "import-reservations.conflict",
+                    &[&(i + 1), &date, &flight_id, &seat_id],
+                ));
+                continue;
+            }
+
+            let reservation_id = self.create_reservation(user_id, date, flight_id, seat_id, price);
+            self.reservations.get_mut(&reservation_id).unwrap().passenger_name =
+                passenger_name.to_string();
+            imported += 1;
+        }
+        self.record_audit(
+            "admin",
+            "import-reservations",
+            format!(
+                "imported={} conflicts={} errors={}",
+                imported,
+                conflicts.len(),
+                errors
+            ),
+        );
+
+        let mut result = vec![self.msg_fmt(
+            "import-reservations.summary",
+            &[&imported, &conflicts.len(), &errors],
+        )];
+        result.extend(conflicts);
+        if self.seat_map_consistency_report().1 > 0 {
+            result.push(self.consistency_check_result());
+        }
+        result.join("\n")
+    }
+
+    fn process_import_users_csv(&mut self, admin_token: &str, csv_data: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("import-users.unauthorized_operation");
+        }
+
+        let mut created = 0;
+        let mut updated = 0;
+        let mut errors = vec![];
+        for (i, line) in csv_data.lines().enumerate() {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let (user_id, passenger_name, age, email, corporate_account_id) = match fields[..] {
+                [user_id, passenger_name, age, email, ref account @ ..]
+                    if !user_id.is_empty()
+                        && !passenger_name.is_empty()
+                        && passenger_name.chars().count() <= MAX_PASSENGER_NAME_CHARS
+                        && email.contains('@')
+                        && account.len() <= 1 =>
+                {
+                    match age.parse::<u32>() {
+                        Ok(age) => {
+                            let account = account.first().copied().filter(|id| !id.is_empty());
+                            (user_id, passenger_name, age, email, account)
+                        }
+                        Err(_) => {
+                            errors.push(self.msg_fmt("import-users.malformed", &[&(i + 1)]));
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    errors.push(self.msg_fmt("import-users.malformed", &[&(i + 1)]));
+                    continue;
+                }
+            };
+
+            match self.user_profiles.contains_key(user_id) {
+                true => updated += 1,
+                false => created += 1,
+            }
+            let profile = self.user_profiles.entry(user_id.to_string()).or_default();
+            profile.passenger_name = Some(passenger_name.to_string());
+            profile.age = Some(age);
+            profile.email = Some(email.to_string());
+            let previous_account = std::mem::replace(
+                &mut profile.corporate_account_id,
+                corporate_account_id.map(str::to_string),
+            );
+            if let Some(members) = previous_account
+                .and_then(|account_id| self.corporate_accounts.get_mut(&account_id))
+            {
+                members.retain(|member| member != user_id);
+            }
+            if let Some(account_id) = corporate_account_id {
+                self.corporate_accounts
+                    .entry(account_id.to_string())
+                    .or_default()
+                    .push(user_id.to_string());
+            }
+            self.alert_prefs
+                .entry(user_id.to_string())
+                .or_insert_with(|| AlertPreference {
+                    user_id: user_id.to_string(),
+                    confirmation: true,
+                    reminder_hours_before: vec![DEFAULT_REMINDER_HOURS],
+                    cancellation_notice: true,
+                });
+        }
+        self.record_audit(
+            "admin",
+            "import-users",
+            format!("created={} updated={} errors={}", created, updated, errors.len()),
+        );
+
+        let mut result = vec![self.msg_fmt(
+            "import-users.summary",
+            &[&created, &updated, &errors.len()],
+        )];
+        result.extend(errors);
+        result.join("\n")
+    }
+
+    fn process_get_profile(&self, user_id: &str) -> String {
+        let profile = match self.user_profiles.get(user_id) {
+            Some(profile) => profile,
+            None => return self.msg("get-profile.user_not_found"),
+        };
+        let unset = || "-".to_string();
+        let seat_types: String =
+            profile.preferred_seat_types.iter().map(|seat_type| seat_type.as_char()).collect();
+        let account = match &profile.corporate_account_id {
+            Some(account_id) => format!(
+                "{} ({} members)",
+                account_id,
+                self.corporate_accounts.get(account_id).map_or(0, Vec::len)
+            ),
+            None => unset(),
+        };
+        format!(
+            "get-profile: {} name={} age={} email={} corporate={} seat_types={}",
+            user_id,
+            profile.passenger_name.clone().unwrap_or_else(unset),
+            profile.age.map_or_else(unset, |age| age.to_string()),
+            profile.email.clone().unwrap_or_else(unset),
+            account,
+            if seat_types.is_empty() { unset() } else { seat_types }
+        )
+    }
+
+    fn find_orphaned_seat_locks(&self) -> Vec<(String, u32, String)> {
+        let held: HashSet<(&str, u32, &str)> = self
+            .reservations
+            .values()
+            .filter(|reservation| !reservation.is_cancelled)
+            .map(|reservation| {
+                (reservation.date.as_str(), reservation.flight_id, reservation.seat_id.as_str())
+            })
+            .collect();
+
+        let mut orphans = vec![];
+        for (date, flights_on_date) in &self.seat_reservations {
+            for (&flight_id, seats) in flights_on_date {
+                for (seat_id, state) in seats {
+                    let orphaned = match state {
+                        SeatState::Free => false,
+                        SeatState::Reserved => {
+                            !held.contains(&(date.as_str(), flight_id, seat_id.as_str()))
+                        }
+                        SeatState::TentativeLock(lock_id) => !self.seat_locks.contains_key(lock_id),
+                    };
+                    if orphaned {
+                        orphans.push((date.clone(), flight_id, seat_id.clone()));
+                    }
+                }
+            }
+        }
+        orphans.sort();
+        orphans
+    }
+
+    fn process_fix_orphaned_locks(&mut self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("fix-orphaned-locks.unauthorized_operation");
+        }
+
+        let orphans = self.find_orphaned_seat_locks();
+        for (date, flight_id, seat_id) in &orphans {
+            self.unreserve_seat(date, *flight_id, seat_id);
+        }
+        self.record_audit("admin", "fix-orphaned-locks", format!("removed={}", orphans.len()));
+        self.msg_fmt("fix-orphaned-locks.success", &[&orphans.len()])
+    }
+
+    fn seat_map_consistency_report(&self) -> (usize, usize, Vec<String>) {
+        let mut holders: HashMap<(&str, u32, &str), Vec<u32>> = HashMap::new();
+        for reservation in self.reservations.values() {
+            if reservation.is_cancelled || reservation.parent_reservation_id.is_some() {
+                continue;
+            }
+            holders
+                .entry((reservation.date.as_str(), reservation.flight_id, &reservation.seat_id))
+                .or_default()
+                .push(reservation.reservation_id);
+        }
+
+        let mut reserved_seats = vec![];
+        for (date, flights_on_date) in &self.seat_reservations {
+            for (&flight_id, seats) in flights_on_date {
+                for (seat_id, &state) in seats {
+                    if state == SeatState::Reserved {
+                        reserved_seats.push((date.as_str(), flight_id, seat_id.as_str()));
+                    }
+                }
+            }
+        }
+        reserved_seats.sort();
+
+        let mut consistent = 0;
+        let mut details = vec![];
+        for &(date, flight_id, seat_id) in &reserved_seats {
+            match holders.get(&(date, flight_id, seat_id)).map(Vec::len) {
+                None => details.push(self.msg_fmt(
+                    "consistency-check.seat_without_reservation",
+                    &[&date, &flight_id, &seat_id],
+                )),
+                Some(1) => consistent += 1,
+                Some(count) => details.push(self.msg_fmt(
+                    "consistency-check.seat_held_twice",
+                    &[&date, &flight_id, &seat_id, &count],
+                )),
+            }
+        }
+
+        let mut reservation_ids: Vec<u32> = holders
+            .iter()
+            .filter(|(&(date, flight_id, seat_id), _)| {
+                self.seat_state(date, flight_id, seat_id) != SeatState::Reserved
+            })
+            .flat_map(|(_, reservation_ids)| reservation_ids.iter().copied())
+            .collect();
+        reservation_ids.sort();
+        for reservation_id in reservation_ids {
+            let reservation = &self.reservations[&reservation_id];
+            details.push(self.msg_fmt(
+                "consistency-check.reservation_without_seat",
+                &[&reservation_id, &reservation.date, &reservation.flight_id, &reservation.seat_id],
+            ));
+        }
+        (consistent, details.len(), details)
+    }
+
+    fn process_repair_partial_import(&mut self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("repair-partial-import.unauthorized_operation");
+        }
+
+        let mut incomplete_flights: Vec<u32> = self
+            .flights
+            .values()
+            .filter(|flight| {
+                flight.seat_classes.is_empty()
+                    && !self
+                        .reservations
+                        .values()
+                        .any(|reservation| reservation.flight_id == flight.flight_id)
+            })
+            .map(|flight| flight.flight_id)
+            .collect();
+        incomplete_flights.sort();
+        for flight_id in &incomplete_flights {
+            self.flights.remove(flight_id);
+            self.record_audit(
+                "admin",
+                "repair-partial-import",
+                format!("removed_flight_id={}", flight_id),
+            );
+        }
+        if !incomplete_flights.is_empty() {
+            self.clear_search_cache();
+        }
+
+        let mut orphaned: Vec<(String, u32)> = self
+            .seat_reservations
+            .iter()
+            .flat_map(|(date, flights_on_date)| {
+                flights_on_date.keys().map(move |&flight_id| (date.clone(), flight_id))
+            })
+            .filter(|(_, flight_id)| !self.flights.contains_key(flight_id))
+            .collect();
+        orphaned.sort();
+        let mut orphan_locks = 0;
+        for (date, flight_id) in &orphaned {
+            let flights_on_date = self.seat_reservations.get_mut(date).unwrap();
+            let seats = flights_on_date.remove(flight_id).unwrap();
+            if flights_on_date.is_empty() {
+                self.seat_reservations.remove(date);
+            }
+            orphan_locks += seats.len();
+            self.record_audit(
+                "admin",
+                "repair-partial-import",
+                format!("cleaned date={} flight_id={} seats={}", date, flight_id, seats.len()),
+            );
+        }
+
+        let inconsistent = self.seat_map_consistency_report().1;
+        let consistency = match inconsistent {
+            0 => "ok".to_string(),
+            n => n.to_string(),
+        };
+        self.msg_fmt(
+            "repair-partial-import.success",
+            &[&incomplete_flights.len(), &orphan_locks, &consistency],
+        )
+    }
+
+    fn process_consistency_check(&self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("consistency-check.unauthorized_operation");
+        }
+        self.consistency_check_result()
+    }
+
+    fn consistency_check_result(&self) -> String {
+        let (consistent, inconsistent, details) = self.seat_map_consistency_report();
+        let summary = self.msg_fmt("consistency-check.summary", &[&consistent, &inconsistent]);
+        let mut result = vec![summary];
+        result.extend(details);
+        result.join("\n")
+    }
+
+    fn process_reservation_debug(&self, admin_token: &str, reservation_id: u32) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("reservation-debug.unauthorized_operation");
+        }
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) => reservation,
+            None => return self.msg("reservation-debug.reservation_not_found"),
+        };
+
+        let text = |value: &str| JsonValue::String(value.to_string());
+        let number = |value: u32| JsonValue::Number(value as f64);
+        let object = |entries: Vec<(&str, JsonValue)>| {
+            JsonValue::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect(),
+            )
+        };
+        let optional_text =
+            |value: &Option<String>| value.as_deref().map_or(JsonValue::Null, text);
+
+        let flight = &self.flights[&reservation.flight_id];
+        let seat_classes = flight
+            .seat_classes
+            .iter()
+            .enumerate()
+            .map(|(i, seat_class)| {
+                object(vec![
+                    ("class", number(i as u32 + 1)),
+                    ("last_row", number(seat_class.column)),
+                    ("price", number(seat_class.price)),
+                ])
+            })
+            .collect();
+        let flight_json = object(vec![
+            ("flight_id", number(flight.flight_id)),
+            ("departure_airport", number(flight.departure_airport)),
+            ("arrival_airport", number(flight.arrival_airport)),
+            ("departure_time", text(&flight.departure_time)),
+            ("arrival_time", text(&flight.arrival_time)),
+            ("service_date", optional_text(&flight.service_date)),
+            ("fuel_surcharge", number(flight.fuel_surcharge)),
+            ("is_removed", JsonValue::Bool(flight.is_removed)),
+            ("seat_classes", JsonValue::Array(seat_classes)),
+        ]);
+        let seat_class_json =
+            flight.get_seat_class(&reservation.seat_id).map_or(JsonValue::Null, |(i, price)| {
+                object(vec![("class", number(i)), ("price", number(price))])
+            });
+
+        let itinerary_json = self
+            .itinerary_of(reservation_id)
+            .map_or(JsonValue::Null, |itinerary_id| {
+                let itinerary = &self.itineraries[&itinerary_id];
+                let reservation_ids =
+                    itinerary.reservation_ids.iter().map(|&id| number(id)).collect();
+                object(vec![
+                    ("itinerary_id", number(itinerary_id)),
+                    ("reservation_ids", JsonValue::Array(reservation_ids)),
+                ])
+            });
+        let profile_json = self
+            .user_profiles
+            .get(&reservation.user_id)
+            .map_or(JsonValue::Null, |profile| {
+                let seat_types: String = profile
+                    .preferred_seat_types
+                    .iter()
+                    .map(|seat_type| seat_type.as_char())
+                    .collect();
+                object(vec![
+                    ("passenger_name", optional_text(&profile.passenger_name)),
+                    ("age", profile.age.map_or(JsonValue::Null, number)),
+                    ("email", optional_text(&profile.email)),
+                    ("corporate_account_id", optional_text(&profile.corporate_account_id)),
+                    ("preferred_seat_types", text(&seat_types)),
+                ])
+            });
+        let promo_json = reservation.promo_code.as_ref().map_or(JsonValue::Null, |code| {
+            let mut entries = vec![("code", text(code))];
+            if let Some(promo) = self.promo_codes.get(code) {
+                let discount = match promo.discount {
+                    PromoDiscount::Percent(pct) => format!("{}%", pct),
+                    PromoDiscount::Fixed(amount) => amount.to_string(),
+                };
+                entries.extend([
+                    ("discount", text(&discount)),
+                    ("uses", number(promo.uses)),
+                    ("max_uses", number(promo.max_uses)),
+                    ("expiry_date", text(&promo.expiry_date.format("%Y/%m/%d").to_string())),
+                ]);
+            }
+            object(entries)
+        });
+        let insurance_json = self
+            .insurance_policies
+            .values()
+            .find(|policy| policy.reservation_id == reservation_id)
+            .map_or(JsonValue::Null, |policy| {
+                object(vec![
+                    ("policy_id", number(policy.policy_id)),
+                    ("coverage_type", text(&policy.coverage_type.to_string())),
+                    ("premium", number(policy.premium)),
+                    ("is_claimed", JsonValue::Bool(policy.is_claimed)),
+                ])
+            });
+
+        let mut swap_requests: Vec<&SwapRequest> = self
+            .swap_requests
+            .values()
+            .filter(|request| {
+                request.status == SwapStatus::Pending
+                    && (request.initiator_reservation_id == reservation_id
+                        || request.target_reservation_id == reservation_id)
+            })
+            .collect();
+        swap_requests.sort_by_key(|request| request.request_id);
+        let swap_requests = swap_requests
+            .into_iter()
+            .map(|request| {
+                object(vec![
+                    ("request_id", number(request.request_id)),
+                    ("initiator_user_id", text(&request.initiator_user_id)),
+                    ("initiator_reservation_id", number(request.initiator_reservation_id)),
+                    ("target_user_id", text(&request.target_user_id)),
+                    ("target_reservation_id", number(request.target_reservation_id)),
+                    (
+                        "expires_at",
+                        text(&request.expires_at.format("%Y/%m/%d-%H:%M:%S").to_string()),
+                    ),
+                ])
+            })
+            .collect();
+        let mut vouchers: Vec<&RefundVoucher> = self
+            .vouchers
+            .values()
+            .filter(|voucher| voucher.reservation_id == reservation_id)
+            .collect();
+        vouchers.sort_by_key(|voucher| voucher.voucher_id);
+        let vouchers = vouchers
+            .into_iter()
+            .map(|voucher| {
+                object(vec![
+                    ("voucher_id", number(voucher.voucher_id)),
+                    ("user_id", text(&voucher.user_id)),
+                    ("amount", number(voucher.amount)),
+                ])
+            })
+            .collect();
+        let miles = self.miles.get(&reservation.user_id);
+
+        let debug = object(vec![
+            (
+                "reservation",
+                object(vec![
+                    ("reservation_id", number(reservation_id)),
+                    ("user_id", text(&reservation.user_id)),
+                    ("passenger_name", text(&reservation.passenger_name)),
+                    ("date", text(&reservation.date)),
+                    ("seat_id", text(&reservation.seat_id)),
+                    ("base_price", number(reservation.base_price)),
+                    ("service_fee", number(reservation.service_fee)),
+                    ("total_price", number(reservation.total_price)),
+                    ("is_cancelled", JsonValue::Bool(reservation.is_cancelled)),
+                    ("is_checked_in", JsonValue::Bool(reservation.is_checked_in)),
+                    (
+                        "pending_fare_adjustment",
+                        JsonValue::Number(reservation.pending_fare_adjustment as f64),
+                    ),
+                    ("payment_method", optional_text(&reservation.payment_method)),
+                    ("group_discount", number(reservation.group_discount)),
+                    ("fare_discount", number(reservation.fare_discount)),
+                    (
+                        "original_price",
+                        reservation.original_price.map_or(JsonValue::Null, number),
+                    ),
+                ]),
+            ),
+            ("flight", flight_json),
+            ("seat_class", seat_class_json),
+            ("itinerary", itinerary_json),
+            ("user_profile", profile_json),
+            (
+                "miles",
+                object(vec![
+                    ("total_earned", number(miles.map_or(0, MilesLedger::total_earned))),
+                    (
+                        "active",
+                        number(miles.map_or(0, |miles| miles.active_balance(self.clock.date()))),
+                    ),
+                    (
+                        "from_reservation",
+                        number(miles.map_or(0, |miles| miles.earned_from(reservation_id))),
+                    ),
+                ]),
+            ),
+            ("promo_code", promo_json),
+            ("insurance", insurance_json),
+            ("swap_requests", JsonValue::Array(swap_requests)),
+            ("vouchers", JsonValue::Array(vouchers)),
+        ]);
+
+        format!("reservation-debug: {}\n{}", reservation_id, debug.to_pretty_string(0))
+    }
+
+    fn generate_test_flight(rng: &mut Lcg, flight_id: u32) -> Flight {
+        let departure_airport = 100 + rng.next_below(900) as u32;
+        let arrival_airport = 100 + (departure_airport - 100 + 1 + rng.next_below(899) as u32) % 900;
+        let departure_hour = rng.next_below(20);
+        let minute = rng.next_below(4) * 15;
+        let arrival_hour = departure_hour + 1 + rng.next_below(4);
+
+        let class_count = 1 + rng.next_below(3) as u32;
+        let mut column = 0;
+        let mut seat_classes = vec![];
+        for i in 0..class_count {
+            column = match i + 1 == class_count {
+                true => SEAT_ROWS,
+                false => {
+                    let max_step = SEAT_ROWS - column - (class_count - i - 1);
+                    column + 1 + rng.next_below(max_step as u64) as u32
+                }
+            };
+            let price = (class_count - i) * 10000 + rng.next_below(50) as u32 * 100;
+            seat_classes.push((column, price));
+        }
+
+        FlightBuilder::new(flight_id)
+            .route(departure_airport, arrival_airport)
+            .schedule(
+                format!("{:02}:{:02}:00", departure_hour, minute),
+                format!("{:02}:{:02}:00", arrival_hour, minute),
+            )
+            .with_seat_class(SeatClassBuilder::from(seat_classes))
+            .build()
+    }
+
+    fn process_generate_test_data(
+        &mut self,
+        admin_token: &str,
+        n_flights: u32,
+        n_users: u32,
+        n_reservations: u32,
+        seed: u64,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("generate-test-data.unauthorized_operation");
+        }
+        if n_flights == 0
+            || n_flights > TEST_DATA_MAX_FLIGHTS
+            || n_reservations > TEST_DATA_MAX_RESERVATIONS
+            || (n_reservations > 0 && n_users == 0)
+        {
+            return self.msg("generate-test-data.invalid_count");
+        }
+        self.test_data_seed = Some(seed);
+        let mut rng = Lcg::new(seed);
+
+        let first_flight_id = self.flights.keys().max().map_or(1, |flight_id| flight_id + 1);
+        let flight_ids: Vec<u32> = (first_flight_id..first_flight_id + n_flights).collect();
+        for &flight_id in &flight_ids {
+            let flight = Self::generate_test_flight(&mut rng, flight_id);
+            self.add_flight(flight);
+        }
+
+        let start_date = self.clock.date() + Duration::days(1);
+        let mut created = 0;
+        for _ in 0..n_reservations as u64 * 10 {
+            if created == n_reservations {
+                break;
+            }
+            let flight_id = flight_ids[rng.next_below(flight_ids.len() as u64) as usize];
+            let date = (start_date + Duration::days(rng.next_below(TEST_DATA_DAYS) as i64))
+                .format("%Y/%m/%d")
+                .to_string();
+            let user_id = format!("user{}", 1 + rng.next_below(n_users as u64));
+            let flight = &self.flights[&flight_id];
+            let rows = flight.seat_classes.last().unwrap().column as u64;
+            let seat_types = SeatType::variants();
+            let seat_id = format!(
+                "{}{}",
+                1 + rng.next_below(rows),
+                seat_types[rng.next_below(seat_types.len() as u64) as usize].as_char()
+            );
+            let price = match flight.get_seat_class(&seat_id) {
+                Some((_, price)) => price + flight.fuel_surcharge,
+                None => continue,
+            };
+            if self.is_seat_reserved(&date, flight_id, &seat_id) {
+                continue;
+            }
+            self.create_reservation(&user_id, &date, flight_id, &seat_id, price);
+            created += 1;
+        }
+
+        self.record_audit(
+            "admin",
+            "generate-test-data",
+            format!(
+                "seed={} flights={} users={} reservations={}",
+                self.test_data_seed.unwrap_or(seed),
+                n_flights,
+                n_users,
+                created
+            ),
+        );
+        self.msg_fmt("generate-test-data.success", &[&n_flights, &n_users, &created])
+    }
+
+    fn process_reset_reservation_counter(&mut self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("reset-counter.unauthorized_operation");
+        }
+        self.next_reservation_id = self.reservations.keys().max().map_or(1, |max| max + 1);
+        self.record_audit(
+            "admin",
+            "reset-counter",
+            format!("next_reservation_id={}", self.next_reservation_id),
+        );
+        self.msg_fmt("reset-counter.success", &[&self.next_reservation_id])
+    }
+
+    fn process_remove_flight(&mut self, admin_token: &str, flight_id: u32) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("remove-flight.unauthorized_operation");
+        }
+        match self.flights.get_mut(&flight_id) {
+            Some(flight) if !flight.is_removed => flight.is_removed = true,
+            _ => return self.msg("remove-flight.flight_not_found"),
+        }
+        self.clear_search_cache();
+        self.record_audit("admin", "remove-flight", format!("flight_id={}", flight_id));
+
+        let mut reservation_ids: Vec<u32> = self
+            .reservations
+            .values()
+            .filter(|reservation| reservation.flight_id == flight_id && !reservation.is_cancelled)
+            .map(|reservation| reservation.reservation_id)
+            .collect();
+        reservation_ids.sort();
+        for &reservation_id in &reservation_ids {
+            self.record_event(|| SystemEvent::ReservationCancelled { reservation_id });
+            let reservation = self.reservations.get_mut(&reservation_id).unwrap();
+            reservation.is_cancelled = true;
+            let date = reservation.date.clone();
+            let seat_id = reservation.seat_id.clone();
+            self.unreserve_seat(&date, flight_id, &seat_id);
+            self.remove_itinerary_if_cancelled(reservation_id);
+            self.notify_reservation_cancelled(reservation_id);
+        }
+        let claims = self.process_auto_claim_insurance_on_flight_cancel(&reservation_ids);
+
+        self.msg_fmt("remove-flight.success", &[&reservation_ids.len(), &claims])
+    }
+
+    fn process_auto_claim_insurance_on_flight_cancel(&mut self, reservation_ids: &[u32]) -> u32 {
+        let cancelled: HashSet<u32> = reservation_ids.iter().copied().collect();
+        let mut policy_ids: Vec<u32> = self
+            .insurance_policies
+            .values()
+            .filter(|policy| {
+                !policy.is_claimed
+                    && policy.coverage_type != CoverageType::DelayCompensation
+                    && cancelled.contains(&policy.reservation_id)
+            })
+            .map(|policy| policy.policy_id)
+            .collect();
+        policy_ids.sort();
+
+        for &policy_id in &policy_ids {
+            let policy = self.insurance_policies.get_mut(&policy_id).unwrap();
+            policy.is_claimed = true;
+            let reservation = &self.reservations[&policy.reservation_id];
+            let reservation_id = reservation.reservation_id;
+            let user_id = reservation.user_id.clone();
+            let amount = reservation.base_price;
+            let voucher_id = self.issue_voucher(&user_id, reservation_id, amount);
+            self.record_audit(
+                "system",
+                "auto-claim-insurance",
+                format!(
+                    "policy_id={} reservation_id={} voucher_id={} amount={}",
+                    policy_id, reservation_id, voucher_id, amount
+                ),
+            );
+        }
+        policy_ids.len() as u32
+    }
+
+    fn process_get_all_seats(&self, admin_token: &str, user_id: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("get-all-seats.unauthorized_operation");
+        }
+        let mut reservations: Vec<&Reservation> = self
+            .reservations
+            .values()
+            .filter(|reservation| reservation.user_id == user_id)
+            .collect();
+        reservations.sort_by(|a, b| {
+            a.flight_id
+                .cmp(&b.flight_id)
+                .then_with(|| self.parse_date(&a.date).cmp(&self.parse_date(&b.date)))
+                .then(a.reservation_id.cmp(&b.reservation_id))
+        });
+        let cancelled = reservations
+            .iter()
+            .filter(|reservation| reservation.is_cancelled)
+            .count();
+
+        let mut result = vec![self.msg_fmt(
+            "get-all-seats.summary",
+            &[&user_id, &(reservations.len() - cancelled), &cancelled],
+        )];
+        let mut current_flight = None;
+        for reservation in reservations {
+            if current_flight != Some(reservation.flight_id) {
+                current_flight = Some(reservation.flight_id);
+                result.push(format!("flight {}:", reservation.flight_id));
+            }
+            let status = if reservation.is_cancelled { "cancelled" } else { "active" };
+            result.push(format!("  {} {} ({})", reservation.date, reservation.seat_id, status));
+        }
+        result.join("\n")
+    }
+
+    fn process_set_exchange_rate(
+        &mut self,
+        admin_token: &str,
+        currency: &str,
+        rate: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-exchange-rate.unauthorized_operation");
+        }
+        if !is_currency_code(currency) || currency == BASE_CURRENCY || rate == 0 {
+            return self.msg("set-exchange-rate.invalid_rate");
+        }
+
+        self.exchange_rates.insert(currency.to_string(), rate);
+        self.record_audit(
+            "admin",
+            "set-exchange-rate",
+            format!("currency={} rate={}", currency, rate),
+        );
+        self.msg_fmt("set-exchange-rate.success", &[&currency, &rate, &BASE_CURRENCY])
+    }
+
+    fn process_wallet_deposit(
+        &mut self,
+        admin_token: &str,
+        user_id: &str,
+        currency: &str,
+        amount: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("wallet-deposit.unauthorized_operation");
+        }
+        if !self.exchange_rates.contains_key(currency) {
+            return self.msg("wallet-deposit.unknown_currency");
+        }
+        if amount == 0 {
+            return self.msg("wallet-deposit.invalid_amount");
+        }
+
+        let wallet = self
+            .wallets
+            .entry(user_id.to_string())
+            .or_insert_with(|| UserWallet {
+                user_id: user_id.to_string(),
+                balances: HashMap::new(),
+            });
+        let balance = wallet.balances.entry(currency.to_string()).or_insert(0);
+        *balance = balance.saturating_add(amount);
+        let (owner, balance) = (wallet.user_id.clone(), *balance);
+        self.record_audit(
+            "admin",
+            "wallet-deposit",
+            format!("user_id={} currency={} amount={}", user_id, currency, amount),
+        );
+        self.msg_fmt("wallet-deposit.success", &[&owner, &balance, &currency])
+    }
+
+    fn process_wallet_pay(&mut self, user_id: &str, reservation_id: u32, currency: &str) -> String {
+        let reservation = match self.reservations.get(&reservation_id) {
+            Some(reservation) if !reservation.is_cancelled => reservation,
+            _ => return self.msg("wallet-pay.reservation_not_found"),
+        };
+        if reservation.user_id != user_id {
+            return self.msg("wallet-pay.unauthorized_operation");
+        }
+        if reservation.payment_method.is_some() {
+            return self.msg("wallet-pay.already_paid");
+        }
+        let rate = match self.exchange_rates.get(currency) {
+            Some(&rate) => rate,
+            None => return self.msg("wallet-pay.unknown_currency"),
+        };
+
+        let debits = self.outstanding_debits(user_id);
+        let need = (reservation.total_price + debits).div_ceil(rate);
+        let have = self
+            .wallets
+            .get(user_id)
+            .and_then(|wallet| wallet.balances.get(currency))
+            .copied()
+            .unwrap_or(0);
+        if have < need {
+            return self.msg_fmt("wallet-pay.insufficient_balance", &[&currency, &have, &need]);
+        }
+
+        let wallet = self.wallets.get_mut(user_id).unwrap();
+        *wallet.balances.get_mut(currency).unwrap() -= need;
+        self.reservations.get_mut(&reservation_id).unwrap().payment_method =
+            Some(format!("wallet:{}", currency));
+        let mut result = self.msg_fmt("wallet-pay.success", &[&reservation_id, &need, &currency]);
+        if debits > 0 {
+            for entry in &mut self.debit_log {
+                if entry.user_id == user_id {
+                    entry.is_settled = true;
+                }
+            }
+            result.push('\n');
+            result.push_str(&self.msg_fmt("notice.debits_settled", &[&debits]));
+        }
+        result
+    }
+
+    fn process_wallet_balance(&self, user_id: &str) -> String {
+        let mut balances: Vec<(&String, &u32)> = self
+            .wallets
+            .get(user_id)
+            .map(|wallet| wallet.balances.iter().collect())
+            .unwrap_or_default();
+        balances.sort();
+
+        let mut result = vec![format!("wallet-balance: {}", balances.len())];
+        for (currency, amount) in balances {
+            result.push(format!("{} {}", currency, amount));
+        }
+        result.join("\n")
+    }
+
+    fn process_get_miles(&self, user_id: &str, current_datetime: Option<&str>) -> String {
+        let current_date = match current_datetime {
+            Some(current_datetime) => match NaiveDateTime::parse_from_str(
+                current_datetime,
+                "%Y/%m/%d-%H:%M:%S",
+            ) {
+                Ok(dt) => dt.date(),
+                Err(_) => return self.msg("get-miles.invalid_datetime"),
+            },
+            None => self.clock.date(),
+        };
+        let (total, active) = match self.miles.get(user_id) {
+            Some(miles) => (miles.total_earned(), miles.active_balance(current_date)),
+            None => (0, 0),
+        };
+        format!("get-miles: total={} active={}", total, active)
+    }
+
+    fn process_expire_miles(&mut self, current_datetime: &str) -> String {
+        let current_dt = match NaiveDateTime::parse_from_str(
+            current_datetime,
+            "%Y/%m/%d-%H:%M:%S",
+        ) {
+            Ok(dt) => dt,
+            Err(_) => return self.msg("expire-miles.invalid_datetime"),
+        };
+
+        let mut users_affected = 0;
+        let mut total_expired = 0;
+        for miles in self.miles.values_mut() {
+            let expired = miles.expire(current_dt.date());
+            if expired > 0 {
+                users_affected += 1;
+                total_expired += expired;
+            }
+        }
+        self.record_audit(
+            "system",
+            "expire-miles",
+            format!("users={} miles={}", users_affected, total_expired),
+        );
+        self.msg_fmt("expire-miles.success", &[&users_affected, &total_expired])
+    }
+
+    fn process_merge_users(
+        &mut self,
+        admin_token: &str,
+        primary_user_id: &str,
+        secondary_user_id: &str,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("merge-users.unauthorized_operation");
+        }
+        if primary_user_id == secondary_user_id
+            || primary_user_id == REDACTED
+            || secondary_user_id == REDACTED
+        {
+            return self.msg("merge-users.invalid_user");
+        }
+        self.record_audit(
+            "admin",
+            "merge-users",
+            format!("primary={} secondary={}", primary_user_id, secondary_user_id),
+        );
+
+        let mut merged_reservations = 0;
+        for reservation in self.reservations.values_mut() {
+            if reservation.user_id == secondary_user_id {
+                reservation.user_id = primary_user_id.to_string();
+                merged_reservations += 1;
+            }
+        }
+        for itinerary in self.itineraries.values_mut() {
+            if itinerary.user_id == secondary_user_id {
+                itinerary.user_id = primary_user_id.to_string();
+            }
+        }
+        let mut merged_vouchers = 0;
+        for voucher in self.vouchers.values_mut() {
+            if voucher.user_id == secondary_user_id {
+                voucher.user_id = primary_user_id.to_string();
+                merged_vouchers += 1;
+            }
+        }
+
+        for queue in self.waitlists.values_mut() {
+            if queue.iter().any(|entry| entry.user_id == primary_user_id) {
+                queue.retain(|entry| entry.user_id != secondary_user_id);
+            }
+            for entry in queue.iter_mut() {
+                if entry.user_id == secondary_user_id {
+                    entry.user_id = primary_user_id.to_string();
+                }
+            }
+        }
+
+        let mut merged_miles = 0;
+        if let Some(secondary) = self.miles.remove(secondary_user_id) {
+            merged_miles = secondary.total_earned();
+            let primary = self.miles.entry(primary_user_id.to_string()).or_default();
+            primary.entries.extend(secondary.entries);
+            primary.expired += secondary.expired;
+        }
+        if let Some(secondary) = self.wallets.remove(secondary_user_id) {
+            let primary = self
+                .wallets
+                .entry(primary_user_id.to_string())
+                .or_insert_with(|| UserWallet {
+                    user_id: primary_user_id.to_string(),
+                    balances: HashMap::new(),
+                });
+            for (currency, amount) in secondary.balances {
+                *primary.balances.entry(currency).or_insert(0) += amount;
+            }
+        }
+        for entry in &mut self.debit_log {
+            if entry.user_id == secondary_user_id {
+                entry.user_id = primary_user_id.to_string();
+            }
+        }
+
+        if let Some(secondary) = self.alert_prefs.remove(secondary_user_id) {
+            match self.alert_prefs.get_mut(primary_user_id) {
+                Some(primary) => {
+                    primary.confirmation |= secondary.confirmation;
+                    primary.cancellation_notice |= secondary.cancellation_notice;
+                    for hours in secondary.reminder_hours_before {
+                        if !primary.reminder_hours_before.contains(&hours) {
+                            primary.reminder_hours_before.push(hours);
+                        }
+                    }
+                }
+                None => {
+                    self.alert_prefs.insert(
+                        primary_user_id.to_string(),
+                        AlertPreference {
+                            user_id: primary_user_id.to_string(),
+                            ..secondary
+                        },
+                    );
+                }
+            }
+        }
+        if let Some(profile) = self.user_profiles.remove(secondary_user_id) {
+            self.user_profiles
+                .entry(primary_user_id.to_string())
+                .or_insert(profile);
+        }
+        for members in self.corporate_accounts.values_mut() {
+            if members.iter().any(|member| member == primary_user_id) {
+                members.retain(|member| member != secondary_user_id);
+            }
+            for member in members.iter_mut() {
+                if member == secondary_user_id {
+                    *member = primary_user_id.to_string();
+                }
+            }
+        }
+        if let Some(notices) = self.auction_notices.remove(secondary_user_id) {
+            self.auction_notices
+                .entry(primary_user_id.to_string())
+                .or_default()
+                .extend(notices);
+        }
+        self.blocked_users.insert(secondary_user_id.to_string());
+
+        self.msg_fmt(
+            "merge-users.success",
+            &[&merged_reservations, &merged_miles, &merged_vouchers, &primary_user_id],
+        )
+    }
+
+    fn process_booking_streak(&self, user_id: &str) -> String {
+        let booking_dates: HashSet<NaiveDate> = self
+            .audit_log
+            .iter()
+            .filter(|entry| entry.operation == "reserve")
+            .filter(|entry| {
+                entry
+                    .details
+                    .strip_prefix("reservation_id=")
+                    .and_then(|id| id.parse::<u32>().ok())
+                    .and_then(|id| self.reservations.get(&id))
+                    .is_some_and(|reservation| {
+                        !reservation.is_cancelled && reservation.user_id == user_id
+                    })
+            })
+            .map(|entry| entry.timestamp.date())
+            .collect();
+        let mut booking_dates: Vec<NaiveDate> = booking_dates.into_iter().collect();
+        booking_dates.sort();
+
+        let mut longest = 0;
+        let mut current = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for date in booking_dates {
+            current = match previous {
+                Some(previous) if date - previous == Duration::days(1) => current + 1,
+                _ => 1,
+            };
+            longest = longest.max(current);
+            previous = Some(date);
+        }
+        if longest == 0 {
+            return self.msg_fmt("booking-streak.none", &[&user_id]);
+        }
+        self.msg_fmt("booking-streak.success", &[&user_id, &longest])
+    }
+
+    fn process_get_audit_log(&self, admin_token: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("audit-log.unauthorized_operation");
+        }
+
+        let mut result = vec![format!("audit-log: {}", self.audit_log.len())];
+        for entry in &self.audit_log {
+            result.push(format!(
+                "{} {} {} {} {}",
+                entry.entry_id,
+                entry.timestamp.format("%Y/%m/%d-%H:%M:%S"),
+                entry.actor,
+                entry.operation,
+                entry.details
+            ));
+        }
+        result.join("\n")
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_query_audit_log(
+        &self,
+        admin_token: &str,
+        operation_filter: Option<&str>,
+        actor_filter: Option<&str>,
+        from_datetime: Option<&str>,
+        to_datetime: Option<&str>,
+        page: u32,
+        page_size: u32,
+    ) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("query-audit-log.unauthorized_operation");
+        }
+        if page_size == 0 {
+            return self.msg("query-audit-log.invalid_page_size");
+        }
+        let parse = |datetime: Option<&str>| match datetime {
+            Some(datetime) => NaiveDateTime::parse_from_str(datetime, "%Y/%m/%d-%H:%M:%S")
+                .map(Some)
+                .map_err(|_| ()),
+            None => Ok(None),
+        };
+        let (from, to) = match (parse(from_datetime), parse(to_datetime)) {
+            (Ok(from), Ok(to)) => (from, to),
+            _ => return self.msg("query-audit-log.invalid_datetime"),
+        };
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return self.msg("query-audit-log.invalid_range");
+            }
+        }
+
+        let matching: Vec<&AuditEntry> = self
+            .audit_log
+            .iter()
+            .filter(|entry| {
+                operation_filter.is_none_or(|prefix| entry.operation.starts_with(prefix))
+                    && actor_filter.is_none_or(|actor| entry.actor == actor)
+                    && from.is_none_or(|from| entry.timestamp >= from)
+                    && to.is_none_or(|to| entry.timestamp <= to)
+            })
+            .collect();
+
+        let total = matching.len();
+        let page_size = page_size as usize;
+        let total_pages = total.div_ceil(page_size).max(1);
+        if page == 0 || page as usize > total_pages {
+            return self.msg("query-audit-log.page_out_of_range");
+        }
+
+        let start = (page as usize - 1) * page_size;
+        let end = (start + page_size).min(total);
+        let mut result = vec![format!(
+            "audit-log: total={} showing {}–{}",
+            total,
+            if total == 0 { 0 } else { start + 1 },
+            end
+        )];
+        for entry in &matching[start..end] {
+            result.push(format!(
+                "{} {} {} {} {}",
+                entry.entry_id,
+                entry.timestamp.format("%Y/%m/%d-%H:%M:%S"),
+                entry.actor,
+                entry.operation,
+                entry.details
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn price_history_for(&self, flight_id: u32, class_index: usize) -> Vec<&PriceHistoryEntry> {
+        let mut entries: Vec<&PriceHistoryEntry> = self
+            .price_history
+            .iter()
+            .filter(|entry| entry.flight_id == flight_id && entry.class_index == class_index)
+            .collect();
+        entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        entries
+    }
+
+    fn process_price_history(&self, flight_id: u32, class_index: usize) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("price-history.flight_not_found"),
+        };
+        if class_index == 0 || class_index > flight.seat_classes.len() {
+            return self.msg("price-history.invalid_class");
+        }
+
+        let entries = self.price_history_for(flight_id, class_index);
+        let mut result = vec![format!("price-history: {}", entries.len())];
+        for entry in entries {
+            result.push(format!(
+                "{} {} {:.1}%",
+                entry.timestamp, entry.price, entry.occupancy_pct
+            ));
+        }
+        result.join("\n")
+    }
+
+    fn process_price_trend(&self, flight_id: u32, class_index: usize, window_days: u32) -> String {
+        let flight = match self.flights.get(&flight_id) {
+            Some(flight) => flight,
+            None => return self.msg("price-trend.flight_not_found"),
+        };
+        if class_index == 0 || class_index > flight.seat_classes.len() {
+            return self.msg("price-trend.invalid_class");
+        }
+        if window_days == 0 {
+            return self.msg("price-trend.invalid_window");
+        }
+
+        let window_start = (self.clock - Duration::days(window_days as i64))
+            .format("%Y/%m/%d-%H:%M:%S")
+            .to_string();
+        let prices: Vec<u32> = self
+            .price_history_for(flight_id, class_index)
+            .into_iter()
+            .filter(|entry| entry.timestamp >= window_start)
+            .map(|entry| entry.price)
+            .collect();
+        if prices.is_empty() {
+            return self.msg("price-trend.no_data");
+        }
+
+        let average = prices.iter().map(|&price| price as f64).sum::<f64>() / prices.len() as f64;
+        format!(
+            "price-trend: avg={:.0} min={} max={} samples={}",
+            average,
+            prices.iter().min().unwrap(),
+            prices.iter().max().unwrap(),
+            prices.len()
+        )
+    }
+
+    fn enable_recording(&mut self) {
+        self.recording = true;
+        self.command_log.clear();
+    }
+
+    fn disable_recording(&mut self) -> Vec<RecordedCommand> {
+        self.recording = false;
+        std::mem::take(&mut self.command_log)
+    }
+
+    fn replay(&mut self, log: &[RecordedCommand]) -> Vec<String> {
+        let mut outputs = vec![];
+        for recorded in log {
+            let mut payload = recorded.payload.iter().cloned();
+            outputs.push(self.process_query(&recorded.query_line(), &mut payload));
+        }
+        outputs
+    }
+
+    fn load_state(&mut self, path: &str) -> Result<usize, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(format!("error: cannot read state file {}: {}", path, err)),
+        };
+        let lines: Vec<String> = contents.lines().map(str::to_string).collect();
+        let log = RecordedCommand::parse_log(&lines);
+        self.replay(&log);
+        Ok(log.len())
+    }
+
+    fn journal_lines(&self) -> Vec<String> {
+        self.state_journal
+            .iter()
+            .flat_map(RecordedCommand::to_lines)
+            .collect()
+    }
+
+    fn save_state(&self, path: &str) -> Result<(), String> {
+        let mut contents = self.journal_lines().join("\n");
+        contents.push('\n');
+        std::fs::write(path, contents)
+            .map_err(|err| format!("error: cannot write state file {}: {}", path, err))
+    }
+
+    fn save(&self, path: &str) -> Result<(), String> {
+        let mut lines = vec![format!("checkpoint {}", self.processed_query_count)];
+        lines.extend(self.journal_lines());
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, lines.join("\n") + "\n")
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|err| format!("error: cannot write checkpoint {}: {}", path, err))
+    }
+
+    fn load_checkpoint(&mut self, path: &str) -> Result<usize, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(format!("error: cannot read checkpoint {}: {}", path, err)),
+        };
+        let mut lines = contents.lines();
+        let processed_query_count: u32 = lines
+            .next()
+            .and_then(|header| header.strip_prefix("checkpoint "))
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| format!("error: invalid checkpoint {}", path))?;
+        let lines: Vec<String> = lines.map(str::to_string).collect();
+        let log = RecordedCommand::parse_log(&lines);
+        self.replay(&log);
+        self.processed_query_count = processed_query_count;
+        Ok(log.iter().map(|recorded| 1 + recorded.payload.len()).sum())
+    }
+
+    fn finish_input_query(&mut self) -> Result<(), String> {
+        self.processed_query_count += 1;
+        match self.checkpoint_every {
+            Some(every) if self.processed_query_count.is_multiple_of(every) => {
+                self.save(&self.checkpoint_path)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn process_set_checkpoint(&mut self, admin_token: &str, every: u32, path: &str) -> String {
+        if !self.is_admin(admin_token) {
+            return self.msg("set-checkpoint.unauthorized_operation");
+        }
+        if every == 0 {
+            return self.msg("set-checkpoint.invalid_interval");
+        }
+        self.checkpoint_every = Some(every);
+        self.checkpoint_path = path.to_string();
+        self.msg_fmt("set-checkpoint.success", &[&every, &path])
+    }
+
+    fn process_start_recording(&mut self) -> String {
+        if self.recording {
+            return self.msg("start-recording.already_recording");
+        }
+        self.enable_recording();
+        self.msg("start-recording.success")
+    }
+
+    fn process_stop_recording(&mut self) -> String {
+        if !self.recording {
+            return self.msg("stop-recording.not_recording");
+        }
+        let log = self.disable_recording();
+        let mut result = vec![self.msg_fmt("stop-recording.success", &[&log.len()])];
+        result.extend(log.iter().flat_map(RecordedCommand::to_lines));
+        result.join("\n")
+    }
+
+    fn process_query(
+        &mut self,
+        query_line: &str,
+        payload: &mut dyn Iterator<Item = String>,
+    ) -> String {
+        self.query_count += 1;
+        let query_index = self.query_count;
+        let query: Vec<&str> = query_line.split_whitespace().collect();
+        let command = query.first().copied().unwrap_or_default();
+        if let Some(dt) = query
+            .get(1)
+            .and_then(|arg| NaiveDateTime::parse_from_str(arg, "%Y/%m/%d-%H:%M:%S").ok())
+        {
+            self.clock = self.clock.max(dt);
+        }
+
+        let record = self.recording
+            && self.query_depth == 0
+            && !matches!(command, "start-recording:" | "stop-recording:" | "replay:");
+        let mut consumed = vec![];
+        self.query_depth += 1;
+        let outcome = {
+            let mut payload = payload.inspect(|line| consumed.push(line.clone()));
+            self.dispatch(&query, &mut payload)
+        };
+        self.query_depth -= 1;
+        if self.query_depth == 0 && !command.is_empty() {
+            let recorded = RecordedCommand {
+                command: command.to_string(),
+                args: query[1..].iter().map(|arg| arg.to_string()).collect(),
+                payload: consumed,
+            };
+            self.state_journal.push(recorded.clone());
+            if record {
+                self.command_log.push(recorded);
+            }
+        }
+
+        match outcome {
+            Ok(result) => result,
+            Err(err) => {
+                self.failed_commands
+                    .push((query_index, query_line.to_string()));
+                match err {
+                    QueryError::UnknownCommand => match closest_command(command, KNOWN_COMMANDS) {
+                        Some(suggestion) if !command.is_empty() => self.msg_fmt(
+                            "query.unknown_command_suggestion",
+                            &[&command, &suggestion],
+                        ),
+                        _ => self.msg_fmt("query.unknown_command", &[&command]),
+                    },
+                    QueryError::InvalidQuery => self.msg_fmt("query.invalid_query", &[&command]),
+                }
+            }
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        query: &[&str],
+        payload: &mut dyn Iterator<Item = String>,
+    ) -> Result<String, QueryError> {
+        let command = match query.first() {
+            Some(command) => *command,
+            None => return Err(QueryError::UnknownCommand),
+        };
+
+        let result = match command {
+            "reserve:" => {
+                if query.len() < 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let seat_id = query[5];
+                let options = ReserveOptions::parse(&query[6..])?;
+                self.process_reserve_with_options(
+                    datetime, user_id, date, flight_id, seat_id, &options,
+                )
+            }
+            "cancel:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let reservation_id: u32 = parse_arg(query[3])?;
+                self.process_cancel(datetime, user_id, reservation_id)
+            }
+            "seat-search:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let date = query[2];
+                let flight_id: u32 = parse_arg(query[3])?;
+                self.process_seat_search(datetime, date, flight_id)
+            }
+            "get-reservations:" => {
+                if query.len() != 3 && query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let fields_csv = query.get(3).copied();
+                self.process_get_reservations(datetime, user_id, fields_csv)
+            }
+            "calendar-view:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let year: u32 = parse_arg(query[2])?;
+                let month: u32 = parse_arg(query[3])?;
+                self.process_calendar_view(user_id, year, month)
+            }
+            "admin-reservations:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let page: u32 = parse_arg(query[2])?;
+                let page_size: u32 = parse_arg(query[3])?;
+                let filter_cancelled: bool = parse_arg(query[4])?;
+                self.process_admin_get_reservations(admin_token, page, page_size, filter_cancelled)
+            }
+            "flight-search:" => {
+                if query.len() < 5 || query.len() > 7 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let date = query[2];
+                let departure_airport: u32 = parse_arg(query[3])?;
+                let arrival_airport: u32 = parse_arg(query[4])?;
+                let depart_after = query.get(5).copied();
+                let depart_before = query.get(6).copied();
+                self.process_flight_search(
+                    datetime,
+                    date,
+                    departure_airport,
+                    arrival_airport,
+                    depart_after,
+                    depart_before,
+                )
+            }
+            "next-available:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let departure_airport: u32 = parse_arg(query[2])?;
+                let arrival_airport: u32 = parse_arg(query[3])?;
+                self.process_next_available_flight(
+                    current_datetime,
+                    departure_airport,
+                    arrival_airport,
+                )
+            }
+            "block-user:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let user_id = query[2];
+                self.process_block_user(admin_token, user_id)
+            }
+            "unblock-user:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let user_id = query[2];
+                self.process_unblock_user(admin_token, user_id)
+            }
+            "list-blocked-users:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_list_blocked_users(admin_token)
+            }
+            "start-auction:" => {
+                if query.len() != 7 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let admin_token = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let seat_id = query[5];
+                let duration_minutes: u32 = parse_arg(query[6])?;
+                self.process_start_auction(
+                    datetime,
+                    admin_token,
+                    date,
+                    flight_id,
+                    seat_id,
+                    duration_minutes,
+                )
+            }
+            "bid:" => {
+                if query.len() != 7 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let seat_id = query[5];
+                let amount: u32 = parse_arg(query[6])?;
+                self.process_bid(datetime, user_id, date, flight_id, seat_id, amount)
+            }
+            "close-auction:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let admin_token = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let seat_id = query[5];
+                self.process_close_auction(datetime, admin_token, date, flight_id, seat_id)
+            }
+            "set-alert-prefs:" => {
+                if query.len() < 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let prefs_json = query[2..].join(" ");
+                self.process_set_alert_prefs(user_id, &prefs_json)
+            }
+            "due-reminders:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                self.process_get_due_reminders(datetime)
+            }
+            "top-flights:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let top_n: u32 = parse_arg(query[1])?;
+                let date_from = query[2];
+                let date_to = query[3];
+                self.process_top_flights(top_n, date_from, date_to)
+            }
+            "route-statistics:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let departure_airport: u32 = parse_arg(query[1])?;
+                let arrival_airport: u32 = parse_arg(query[2])?;
+                let date_from = query[3];
+                let date_to = query[4];
+                self.process_route_statistics(departure_airport, arrival_airport, date_from, date_to)
+            }
+            "checkin:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let reservation_id: u32 = parse_arg(query[3])?;
+                self.process_checkin(datetime, user_id, reservation_id)
+            }
+            "auto-cancel:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                self.process_auto_cancel_no_checkin(datetime)
+            }
+            "get-vouchers:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                self.process_get_vouchers(user_id)
+            }
+            "flexible-search:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let target_date = query[2];
+                let departure_airport: u32 = parse_arg(query[3])?;
+                let arrival_airport: u32 = parse_arg(query[4])?;
+                let flex_days: u32 = parse_arg(query[5])?;
+                self.process_flexible_flight_search(
+                    datetime,
+                    target_date,
+                    departure_airport,
+                    arrival_airport,
+                    flex_days,
+                )
+            }
+            "set-seat-weights:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let weights_csv = query[2];
+                self.process_set_seat_weights(admin_token, weights_csv)
+            }
+            "adjacent-seats:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let date = query[2];
+                let flight_id: u32 = parse_arg(query[3])?;
+                let num_seats: u32 = parse_arg(query[4])?;
+                let same_row: bool = parse_arg(query[5])?;
+                self.process_find_adjacent_seats(datetime, date, flight_id, num_seats, same_row)
+            }
+            "batch:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let count: usize = parse_arg(query[1])?;
+                let lines: Vec<String> = payload.take(count).collect();
+                if lines.len() != count {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let queries: Vec<&str> = lines.iter().map(String::as_str).collect();
+                let mut result = vec!["batch:".to_string()];
+                result.extend(self.process_batch(&queries));
+                result.join("\n")
+            }
+            "clone-schedule:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let start_date = query[3];
+                let end_date = query[4];
+                let day_mask: u8 = parse_arg(query[5])?;
+                self.process_clone_flight_schedule(
+                    admin_token,
+                    flight_id,
+                    start_date,
+                    end_date,
+                    day_mask,
+                )
+            }
+            "create-itinerary:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let reservation_ids_csv = query[2];
+                self.process_create_itinerary(user_id, reservation_ids_csv)
+            }
+            "auto-create-itinerary:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let date = query[2];
+                self.process_auto_create_itinerary(user_id, date)
+            }
+            "recode-airport:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let old_airport_id: u32 = parse_arg(query[2])?;
+                let new_airport_id: u32 = parse_arg(query[3])?;
+                self.process_recode_airport(admin_token, old_airport_id, new_airport_id)
+            }
+            "set-min-connection-time:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let airport: u32 = parse_arg(query[2])?;
+                let minutes: u32 = parse_arg(query[3])?;
+                self.process_set_min_connection_time(admin_token, airport, minutes)
+            }
+            "delay-compensation:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let original_departure = query[3];
+                let actual_departure = query[4];
+                self.process_compute_delay_compensation(
+                    admin_token,
+                    flight_id,
+                    original_departure,
+                    actual_departure,
+                )
+            }
+            "propagate-delay:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let delay_hours: u32 = parse_arg(query[3])?;
+                self.process_propagate_delay(admin_token, flight_id, delay_hours)
+            }
+            "generate-promos:" => {
+                if query.len() != 8 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let prefix = query[2];
+                let count: u32 = parse_arg(query[3])?;
+                let discount_type = query[4];
+                let value: u32 = parse_arg(query[5])?;
+                let max_uses: u32 = parse_arg(query[6])?;
+                let expiry_date = query[7];
+                self.process_generate_promo_codes(
+                    admin_token,
+                    prefix,
+                    count,
+                    discount_type,
+                    value,
+                    max_uses,
+                    expiry_date,
+                )
+            }
+            "update-class-seat-types:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let class_index: u32 = parse_arg(query[3])?;
+                let seat_types_csv = query[4];
+                self.process_update_class_seat_types(
+                    admin_token,
+                    flight_id,
+                    class_index,
+                    seat_types_csv,
+                )
+            }
+            "add-insurance:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                let coverage_type = query[3];
+                self.process_add_insurance(user_id, reservation_id, coverage_type)
+            }
+            "claim-insurance:" => {
+                if query.len() < 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let policy_id: u32 = parse_arg(query[2])?;
+                let reason = query[3..].join(" ");
+                self.process_claim_insurance(user_id, policy_id, &reason)
+            }
+            "set-seat-preference:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let seat_types_csv = query[2];
+                self.process_set_seat_preference(user_id, seat_types_csv)
+            }
+            "satisfaction-report:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let flight_id: u32 = parse_arg(query[1])?;
+                let date_from = query[2];
+                let date_to = query[3];
+                self.process_satisfaction_report(flight_id, date_from, date_to)
+            }
+            "export-analytics:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let date_from = query[2];
+                let date_to = query[3];
+                self.process_export_analytics(admin_token, date_from, date_to)
+            }
+            "export-analytics-json:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let date_from = query[2];
+                let date_to = query[3];
+                self.process_export_analytics_json(admin_token, date_from, date_to)
+            }
+            "passengers-by-class:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let flight_id: u32 = parse_arg(query[1])?;
+                let date = query[2];
+                self.process_passengers_by_class(flight_id, date)
+            }
+            "boarding-list:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let date = query[3];
+                self.process_boarding_list(admin_token, flight_id, date)
+            }
+            "signed-manifest:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let admin_token = query[2];
+                let flight_id: u32 = parse_arg(query[3])?;
+                let date = query[4];
+                self.process_signed_manifest(datetime, admin_token, flight_id, date)
+            }
+            "verify-manifest:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let count: usize = parse_arg(query[1])?;
+                let lines: Vec<String> = payload.take(count).collect();
+                if lines.len() != count {
+                    return Err(QueryError::InvalidQuery);
+                }
+                self.process_verify_manifest(&lines.join("\n"))
+            }
+            "add-announcement:" => {
+                if query.len() < 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let text = query[3..].join(" ");
+                self.process_add_announcement(admin_token, flight_id, &text)
+            }
+            "remove-announcement:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let index: usize = parse_arg(query[3])?;
+                self.process_remove_announcement(admin_token, flight_id, index)
+            }
+            "set-language:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let lang = query[1];
+                match self.set_language(lang) {
+                    Ok(()) => self.msg("set-language.success"),
+                    Err(_) => self.msg("set-language.unknown_language"),
+                }
+            }
+            "start-recording:" => {
+                if query.len() != 1 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                self.process_start_recording()
+            }
+            "stop-recording:" => {
+                if query.len() != 1 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                self.process_stop_recording()
+            }
+            "replay:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let count: usize = parse_arg(query[1])?;
+                let lines: Vec<String> = payload.take(count).collect();
+                if lines.len() != count {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let log = RecordedCommand::parse_log(&lines);
+                let mut result = vec![self.msg_fmt("replay.success", &[&log.len()])];
+                result.extend(self.replay(&log));
+                result.join("\n")
+            }
+            "delete-user-data:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let user_id = query[2];
+                self.process_delete_user_data(admin_token, user_id)
+            }
+            "set-exchange-rate:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let currency = query[2];
+                let rate: u32 = parse_arg(query[3])?;
+                self.process_set_exchange_rate(admin_token, currency, rate)
+            }
+            "wallet-deposit:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let user_id = query[2];
+                let currency = query[3];
+                let amount: u32 = parse_arg(query[4])?;
+                self.process_wallet_deposit(admin_token, user_id, currency, amount)
+            }
+            "wallet-pay:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                let currency = query[3];
+                self.process_wallet_pay(user_id, reservation_id, currency)
+            }
+            "get-debit-log:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                self.process_get_debit_log(user_id)
+            }
+            "wallet-balance:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                self.process_wallet_balance(user_id)
+            }
+            "get-miles:" => {
+                if query.len() != 2 && query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                self.process_get_miles(user_id, query.get(2).copied())
+            }
+            "expire-miles:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                self.process_expire_miles(current_datetime)
+            }
+            "merge-users:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let primary_user_id = query[2];
+                let secondary_user_id = query[3];
+                self.process_merge_users(admin_token, primary_user_id, secondary_user_id)
+            }
+            "booking-streak:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                self.process_booking_streak(user_id)
+            }
+            "audit-log:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_get_audit_log(admin_token)
+            }
+            "query-audit-log:" => {
+                if query.len() < 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let page: u32 = parse_arg(query[2])?;
+                let page_size: u32 = parse_arg(query[3])?;
+                let (mut operation, mut actor, mut from, mut to) = (None, None, None, None);
+                for token in &query[4..] {
+                    match token.split_once('=') {
+                        Some(("op", value)) if !value.is_empty() => operation = Some(value),
+                        Some(("actor", value)) if !value.is_empty() => actor = Some(value),
+                        Some(("from", value)) => from = Some(value),
+                        Some(("to", value)) => to = Some(value),
+                        _ => return Err(QueryError::InvalidQuery),
+                    }
+                }
+                self.process_query_audit_log(
+                    admin_token,
+                    operation,
+                    actor,
+                    from,
+                    to,
+                    page,
+                    page_size,
+                )
+            }
+            "add-group-rule:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: Option<u32> = match query[2] {
+                    "any" => None,
+                    flight_id => Some(parse_arg(flight_id)?),
+                };
+                let class_index: Option<usize> = match query[3] {
+                    "any" => None,
+                    class_index => Some(parse_arg(class_index)?),
+                };
+                let buy_n: u32 = parse_arg(query[4])?;
+                let free_m: u32 = parse_arg(query[5])?;
+                self.process_add_group_rule(admin_token, flight_id, class_index, buy_n, free_m)
+            }
+            "add-capacity-alert:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let class_index: usize = parse_arg(query[3])?;
+                let threshold_pct: u32 = parse_arg(query[4])?;
+                self.process_add_capacity_alert(admin_token, flight_id, class_index, threshold_pct)
+            }
+            "list-capacity-alerts:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_list_capacity_alerts(admin_token)
+            }
+            "purge-idempotency-keys:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                self.process_purge_idempotency_keys(current_datetime)
+            }
+            "bulk-reserve:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let seat_ids_csv = query[5];
+                self.process_bulk_reserve(datetime, user_id, date, flight_id, seat_ids_csv)
+            }
+            "price-history:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let flight_id: u32 = parse_arg(query[1])?;
+                let class_index: usize = parse_arg(query[2])?;
+                self.process_price_history(flight_id, class_index)
+            }
+            "price-trend:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let flight_id: u32 = parse_arg(query[1])?;
+                let class_index: usize = parse_arg(query[2])?;
+                let window_days: u32 = parse_arg(query[3])?;
+                self.process_price_trend(flight_id, class_index, window_days)
+            }
+            "export-seat-map:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let date = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let col_width: u8 = parse_arg(query[3])?;
+                self.process_export_seat_map(date, flight_id, col_width)
+            }
+            "refund-estimate:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let reservation_id: u32 = parse_arg(query[3])?;
+                self.process_refund_estimate(datetime, user_id, reservation_id)
+            }
+            "set-commission-rate:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let agent_id = query[2];
+                let pct: u32 = parse_arg(query[3])?;
+                self.process_set_commission_rate(admin_token, agent_id, pct)
+            }
+            "agent-commission:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let agent_id = query[2];
+                let date_from = query[3];
+                let date_to = query[4];
+                self.process_agent_commission_report(admin_token, agent_id, date_from, date_to)
+            }
+            "set-utc-offset:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let offset = query[3];
+                self.process_set_utc_offset(admin_token, flight_id, offset)
+            }
+            "export-ical:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let date = query[1];
+                let departure_airport: u32 = parse_arg(query[2])?;
+                let arrival_airport: u32 = parse_arg(query[3])?;
+                let flight_ids_csv = query[4];
+                self.process_export_ical(date, departure_airport, arrival_airport, flight_ids_csv)
+            }
+            "update-surcharge:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let new_surcharge: u32 = parse_arg(query[3])?;
+                self.process_update_fuel_surcharge(admin_token, flight_id, new_surcharge)
+            }
+            "mark-exit-rows:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let rows_csv = query[3];
+                self.process_mark_exit_rows(admin_token, flight_id, rows_csv)
+            }
+            "set-passenger-info:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                let age: u32 = parse_arg(query[3])?;
+                let needs_assistance: bool = parse_arg(query[4])?;
+                self.process_set_passenger_info(user_id, reservation_id, age, needs_assistance)
+            }
+            "bulk-price-update:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let departure_airport: u32 = parse_arg(query[2])?;
+                let arrival_airport: u32 = parse_arg(query[3])?;
+                let class_index: usize = parse_arg(query[4])?;
+                let new_price: u32 = parse_arg(query[5])?;
+                self.process_bulk_price_update(
+                    admin_token,
+                    departure_airport,
+                    arrival_airport,
+                    class_index,
+                    new_price,
+                )
+            }
+            "set-peak-dates:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let dates_csv = query[2];
+                self.process_set_peak_dates(admin_token, dates_csv)
+            }
+            "set-rebook-protection:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let enabled: bool = parse_arg(query[2])?;
+                self.process_set_rebook_protection(admin_token, enabled)
+            }
+            "force-rebook:" => {
+                if query.len() < 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                let target_class: u32 = parse_arg(query[3])?;
+                let reason = query[4..].join(" ");
+                self.process_force_rebook(admin_token, reservation_id, target_class, &reason)
+            }
+            "set-checkpoint:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let every: u32 = parse_arg(query[2])?;
+                let path = query[3];
+                self.process_set_checkpoint(admin_token, every, path)
+            }
+            "import-reservations:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let count: usize = parse_arg(query[2])?;
+                let lines: Vec<String> = payload.take(count).collect();
+                if lines.len() != count {
+                    return Err(QueryError::InvalidQuery);
+                }
+                self.process_import_reservations_csv(admin_token, &lines.join("\n"))
+            }
+            "import-users:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let count: usize = parse_arg(query[2])?;
+                let lines: Vec<String> = payload.take(count).collect();
+                if lines.len() != count {
+                    return Err(QueryError::InvalidQuery);
+                }
+                self.process_import_users_csv(admin_token, &lines.join("\n"))
+            }
+            "get-profile:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                self.process_get_profile(user_id)
+            }
+            "repair-partial-import:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_repair_partial_import(admin_token)
+            }
+            "consistency-check:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_consistency_check(admin_token)
+            }
+            "fix-orphaned-locks:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_fix_orphaned_locks(admin_token)
+            }
+            "reservation-debug:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                self.process_reservation_debug(admin_token, reservation_id)
+            }
+            "generate-test-data:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let n_flights: u32 = parse_arg(query[2])?;
+                let n_users: u32 = parse_arg(query[3])?;
+                let n_reservations: u32 = parse_arg(query[4])?;
+                let seed: u64 = parse_arg(query[5])?;
+                self.process_generate_test_data(
+                    admin_token,
+                    n_flights,
+                    n_users,
+                    n_reservations,
+                    seed,
+                )
+            }
+            "reset-counter:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_reset_reservation_counter(admin_token)
+            }
+            "remove-flight:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                self.process_remove_flight(admin_token, flight_id)
+            }
+            "get-all-seats:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let user_id = query[2];
+                self.process_get_all_seats(admin_token, user_id)
+            }
+            "set-cache-ttl:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let seconds: u64 = parse_arg(query[2])?;
+                self.process_set_cache_ttl(admin_token, seconds)
+            }
+            "clear-cache:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_clear_cache(admin_token)
+            }
+            "set-output-delimiter:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let mut chars = query[2].chars();
+                let delimiter = match (query[2], chars.next(), chars.next()) {
+                    ("space", _, _) => ' ',
+                    (_, Some(delimiter), None) => delimiter,
+                    _ => return Err(QueryError::InvalidQuery),
+                };
+                self.process_set_output_delimiter(admin_token, delimiter)
+            }
+            "adjust-boundary:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let class_index: u32 = parse_arg(query[3])?;
+                let new_column: u32 = parse_arg(query[4])?;
+                self.process_adjust_class_boundary(admin_token, flight_id, class_index, new_column)
+            }
+            "merge-classes:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let class_index_a: u32 = parse_arg(query[3])?;
+                let class_index_b: u32 = parse_arg(query[4])?;
+                self.process_merge_seat_classes(
+                    admin_token,
+                    flight_id,
+                    class_index_a,
+                    class_index_b,
+                )
+            }
+            "forecast-demand:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let date = query[3];
+                self.process_forecast_demand(current_datetime, flight_id, date)
+            }
+            "class-revenue-breakdown:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let flight_id = parse_arg(query[1])?;
+                let class_index = parse_arg(query[2])?;
+                let date_from = query[3];
+                let date_to = query[4];
+                self.process_class_revenue_breakdown(flight_id, class_index, date_from, date_to)
+            }
+            "retroactive-fix:" => {
+                if query.len() != 7 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let flight_id: u32 = parse_arg(query[2])?;
+                let class_index: u32 = parse_arg(query[3])?;
+                let date_from = query[4];
+                let date_to = query[5];
+                let correct_price: u32 = parse_arg(query[6])?;
+                self.process_retroactive_price_fix(
+                    admin_token,
+                    flight_id,
+                    class_index,
+                    date_from,
+                    date_to,
+                    correct_price,
+                )
+            }
+            "simulate-price-change:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let class_index = parse_arg(query[1])?;
+                let delta_pct = parse_arg(query[2])?;
+                let date_from = query[3];
+                let date_to = query[4];
+                self.process_simulate_price_change(class_index, delta_pct, date_from, date_to)
+            }
+            "empty-flights:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let date_from = query[1];
+                let date_to = query[2];
+                self.process_find_empty_flights(date_from, date_to)
+            }
+            "load-factor-report:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let date_from = query[1];
+                let date_to = query[2];
+                self.process_load_factor_report(date_from, date_to)
+            }
+            "export-events:" => {
+                if query.len() != 1 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                self.process_export_events()
+            }
+            "rebuild-from-events:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let event_count: usize = parse_arg(query[1])?;
+                self.process_rebuild_from_events(event_count)
+            }
+            "join-waitlist:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let class_index: u32 = parse_arg(query[5])?;
+                self.process_join_waitlist(current_datetime, user_id, date, flight_id, class_index)
+            }
+            "register-task:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let task_type = TaskType::parse(query[2]).ok_or(QueryError::InvalidQuery)?;
+                let interval_minutes: u32 = parse_arg(query[3])?;
+                self.process_register_task(admin_token, task_type, interval_minutes)
+            }
+            "run-due-tasks:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                self.process_run_due_tasks(current_datetime)
+            }
+            "prune-waitlist:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                self.process_prune_waitlist(current_datetime)
+            }
+            "set-waitlist-max-retry:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let max_retry: u32 = parse_arg(query[2])?;
+                self.process_set_waitlist_max_retry(admin_token, max_retry)
+            }
+            "set-daily-reservation-limit:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let limit = query[2];
+                self.process_set_daily_reservation_limit(admin_token, limit)
+            }
+            "set-rate-limit:" => match query.len() {
+                3 if query[2] == "none" => self.process_clear_rate_limit(query[1]),
+                4 => {
+                    let admin_token = query[1];
+                    let max_requests: u32 = parse_arg(query[2])?;
+                    let window_seconds: u64 = parse_arg(query[3])?;
+                    self.process_set_rate_limit(admin_token, max_requests, window_seconds)
+                }
+                _ => return Err(QueryError::InvalidQuery),
+            },
+            "has-flown:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let departure_airport: u32 = parse_arg(query[3])?;
+                let arrival_airport: u32 = parse_arg(query[4])?;
+                self.process_has_flown(
+                    current_datetime,
+                    user_id,
+                    departure_airport,
+                    arrival_airport,
+                )
+            }
+            "split-reservation:" => {
+                if query.len() < 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let reservation_id: u32 = parse_arg(query[3])?;
+                let child_age: u8 = parse_arg(query[4])?;
+                let child_passenger_name = query[5..].join(" ");
+                self.process_split_reservation(
+                    current_datetime,
+                    user_id,
+                    reservation_id,
+                    &child_passenger_name,
+                    child_age,
+                )
+            }
+            "name-change:" => {
+                if query.len() < 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let reservation_id: u32 = parse_arg(query[3])?;
+                let new_passenger_name = query[4..].join(" ");
+                self.process_name_change(
+                    current_datetime,
+                    user_id,
+                    reservation_id,
+                    &new_passenger_name,
+                )
+            }
+            "create-swap:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let initiator_user_id = query[2];
+                let initiator_reservation_id: u32 = parse_arg(query[3])?;
+                let target_user_id = query[4];
+                let target_reservation_id: u32 = parse_arg(query[5])?;
+                self.process_create_swap_request(
+                    current_datetime,
+                    initiator_user_id,
+                    initiator_reservation_id,
+                    target_user_id,
+                    target_reservation_id,
+                )
+            }
+            "respond-swap:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let target_user_id = query[2];
+                let request_id: u32 = parse_arg(query[3])?;
+                let accept = match query[4] {
+                    "accept" => true,
+                    "reject" => false,
+                    _ => return Err(QueryError::InvalidQuery),
+                };
+                self.process_respond_swap(current_datetime, target_user_id, request_id, accept)
+            }
+            "purge-expired-locks:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                self.process_purge_expired_locks(current_datetime)
+            }
+            "set-lock-expiry-fee:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let fee: u32 = parse_arg(query[2])?;
+                self.process_set_lock_expiry_fee(admin_token, fee)
+            }
+            "lock-seat:" => {
+                if query.len() != 6 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let date = query[3];
+                let flight_id: u32 = parse_arg(query[4])?;
+                let seat_id = query[5];
+                self.process_lock_seat(current_datetime, user_id, date, flight_id, seat_id)
+            }
+            "transfer-lock:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let from_user_id = query[2];
+                let lock_id: u64 = parse_arg(query[3])?;
+                let to_user_id = query[4];
+                self.process_transfer_lock(current_datetime, from_user_id, lock_id, to_user_id)
+            }
+            "confirm-lock:" => {
+                if query.len() != 4 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let lock_id: u64 = parse_arg(query[3])?;
+                self.process_confirm_lock(current_datetime, user_id, lock_id)
+            }
+            "submit-review:" => {
+                if query.len() < 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let current_datetime = query[1];
+                let user_id = query[2];
+                let reservation_id: u32 = parse_arg(query[3])?;
+                let stars: u8 = parse_arg(query[4])?;
+                let comment = query[5..].join(" ");
+                self.process_submit_review(
+                    current_datetime,
+                    user_id,
+                    reservation_id,
+                    stars,
+                    &comment,
+                )
+            }
+            "get-reviews:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let flight_id: u32 = parse_arg(query[1])?;
+                self.process_get_reviews(flight_id)
+            }
+            "set-first-flyer-discount:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let pct: u32 = parse_arg(query[2])?;
+                self.process_set_first_flyer_discount(admin_token, pct)
+            }
+            "set-service-fee:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let fee: u32 = parse_arg(query[2])?;
+                self.process_set_service_fee(admin_token, fee)
+            }
+            "set-fee-refundable:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                let refundable: bool = parse_arg(query[2])?;
+                self.process_set_fee_refundable(admin_token, refundable)
+            }
+            "start-kiosk-session:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let user_id = query[1];
+                let datetime = query[2];
+                self.process_start_kiosk_session(user_id, datetime)
+            }
+            "kiosk-reserve:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let token = query[1];
+                let date = query[2];
+                let flight_id: u32 = parse_arg(query[3])?;
+                let seat_id = query[4];
+                match self.kiosk_user("kiosk-reserve", token) {
+                    Ok(user_id) => {
+                        let datetime = self.clock.format("%Y/%m/%d-%H:%M:%S").to_string();
+                        self.process_reserve(&datetime, &user_id, date, flight_id, seat_id)
+                    }
+                    Err(message) => message,
+                }
+            }
+            "kiosk-cancel:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let token = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                match self.kiosk_user("kiosk-cancel", token) {
+                    Ok(user_id) => {
+                        let datetime = self.clock.format("%Y/%m/%d-%H:%M:%S").to_string();
+                        self.process_cancel(&datetime, &user_id, reservation_id)
+                    }
+                    Err(message) => message,
+                }
+            }
+            "kiosk-checkin:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let token = query[1];
+                let reservation_id: u32 = parse_arg(query[2])?;
+                match self.kiosk_user("kiosk-checkin", token) {
+                    Ok(user_id) => {
+                        let datetime = self.clock.format("%Y/%m/%d-%H:%M:%S").to_string();
+                        self.process_checkin(&datetime, &user_id, reservation_id)
+                    }
+                    Err(message) => message,
+                }
+            }
+            "join-checkin-queue:" => {
+                if query.len() != 5 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                let flight_id = parse_arg(query[3])?;
+                let date = query[4];
+                self.process_join_checkin_queue(datetime, user_id, flight_id, date)
+            }
+            "serve-checkin-queue:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let admin_token = query[2];
+                self.process_serve_checkin_queue(datetime, admin_token)
+            }
+            "queue-wait:" => {
+                if query.len() != 3 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let datetime = query[1];
+                let user_id = query[2];
+                self.process_estimate_queue_wait(datetime, user_id)
+            }
+            "get-failed-commands:" => {
+                if query.len() != 2 {
+                    return Err(QueryError::InvalidQuery);
+                }
+                let admin_token = query[1];
+                self.process_get_failed_commands(admin_token)
+            }
+            _ => return Err(QueryError::UnknownCommand),
+        };
+
+        Ok(result)
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl HttpResponse {
+    fn json(status: u16, body: JsonValue) -> Self {
+        HttpResponse {
+            status,
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+
+    fn empty(status: u16) -> Self {
+        HttpResponse {
+            status,
+            headers: vec![],
+            body: String::new(),
+        }
+    }
+
+    fn with_cors_headers(mut self, cors: &CorsConfig, request_origin: &str) -> HttpResponse {
+        if !cors.allows(request_origin) {
+            return Self::error(403, "origin not allowed");
+        }
+        self.headers.push((
+            "Access-Control-Allow-Origin".to_string(),
+            request_origin.to_string(),
+        ));
+        self.headers.push((
+            "Access-Control-Allow-Methods".to_string(),
+            cors.allowed_methods.join(", "),
+        ));
+        self
+    }
 
-            if self.is_too_late(current_dt, flight_dt) {
-                return "cancel: too late".to_string();
-            }
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(
+            status,
+            JsonValue::Object(vec![("error".to_string(), JsonValue::String(message.to_string()))]),
+        )
+    }
 
+    fn from_result(
+        system: &ReservationSystem,
+        result: &str,
+        success_status: u16,
+        success_body: impl FnOnce(&str) -> JsonValue,
+    ) -> Self {
+        let first_line = result.lines().next().unwrap_or_default();
+        let message = first_line
+            .split_once(": ")
+            .map_or("", |(_, message)| message);
+        let outcome = system
+            .message_key(first_line)
+            .and_then(|key| key.split_once('.'))
+            .map(|(_, outcome)| outcome);
+        let status = match outcome {
+            Some("flight_not_found" | "reservation_not_found") => 404,
+            Some("unauthorized_operation" | "user_blocked") => 403,
+            Some("already_reserved" | "seat_under_auction") => 409,
+            _ => 400,
+        };
+        let is_success = match outcome {
+            Some(outcome) => outcome == "success",
+            None => message
+                .split_whitespace()
+                .next()
+                .is_some_and(|first| first.parse::<u32>().is_ok()),
+        };
+        match is_success {
+            true => Self::json(success_status, success_body(message)),
+            false => Self::error(status, message),
         }
+    }
+}
 
-        let reservation_mut = self.reservations.get_mut(&reservation_id).unwrap();
-        reservation_mut.is_cancelled = true;
-
-        let date = reservation_mut.date.clone();
-        let flight_id = reservation_mut.flight_id;
-        let seat_id = reservation_mut.seat_id.clone();
+struct CorsConfig {
+    allowed_origins: Vec<String>,
+    allowed_methods: Vec<String>,
+}
 
-        self.unreserve_seat(&date, flight_id, &seat_id);
+impl CorsConfig {
+    fn new(allowed_origins: Vec<String>) -> Self {
+        CorsConfig {
+            allowed_origins,
+            allowed_methods: ["GET", "POST", "DELETE", "OPTIONS"]
+                .iter()
+                .map(|method| method.to_string())
+                .collect(),
+        }
+    }
 
-        "cancel: success".to_string()
+    fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == origin)
     }
+}
 
-    fn process_seat_search(
-        &self,
-        _current_datetime: &str,
-        date: &str,
-        flight_id: u32,
-    ) -> String {
-        if !self.flights.contains_key(&flight_id) {
-            return "seat-search: flight not found".to_string();
-        }
+struct HttpHandler {
+    system: ReservationSystem,
+    cors: CorsConfig,
+}
 
-        let flight = self.flights.get(&flight_id).unwrap();
-        let mut result = vec!["seat-search:".to_string()];
-        let mut seats = vec![];
+impl HttpHandler {
+    fn new(system: ReservationSystem, cors: CorsConfig) -> Self {
+        HttpHandler { system, cors }
+    }
 
-        for row in 1..=20 {
-            let mut row_seats = HashMap::new();
-            for seat_type in &SeatType::variants() {
-                let seat_id = format!("{}{}", row, seat_type.as_char());
-                let seat_display = if self.is_seat_reserved(date, flight_id, &seat_id) {
-                    "X".to_string()
-                } else {
-                    let (seat_class, _) = flight.get_seat_class(&seat_id).unwrap();
-                    seat_class.to_string()
-                };
-                row_seats.insert(seat_type.clone(), seat_display);
+    fn percent_decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut decoded = vec![];
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' if i + 2 < bytes.len() => {
+                    let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                    match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                        Some(byte) => {
+                            decoded.push(byte);
+                            i += 3;
+                            continue;
+                        }
+                        None => decoded.push(b'%'),
+                    }
+                }
+                b'+' => decoded.push(b' '),
+                byte => decoded.push(byte),
             }
-            seats.push(row_seats);
+            i += 1;
         }
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
 
-        for seat_type in &SeatType::variants() {
-            let mut row_display = String::new();
-            for row in &seats {
-                row_display.push_str(&row[seat_type]);
+    fn parse_query(query: &str) -> HashMap<String, String> {
+        query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (Self::percent_decode(key), Self::percent_decode(value)),
+                None => (Self::percent_decode(pair), String::new()),
+            })
+            .collect()
+    }
+
+    fn handle_request(
+        &mut self,
+        method: &str,
+        path: &str,
+        body: &str,
+        origin: Option<&str>,
+    ) -> HttpResponse {
+        let response = match origin {
+            Some(origin) if !self.cors.allows(origin) => {
+                return HttpResponse::error(403, "origin not allowed")
             }
-            result.push(row_display);
+            _ if method == "OPTIONS" => HttpResponse::empty(200),
+            _ => self.route_request(method, path, body),
+        };
+        match origin {
+            Some(origin) => response.with_cors_headers(&self.cors, origin),
+            None => response,
         }
-
-        result.join("\n")
     }
 
-    fn process_get_reservations(&self, _current_datetime: &str, user_id: &str) -> String {
-        let mut valid_reservations = vec![];
+    fn route_request(&mut self, method: &str, path: &str, body: &str) -> HttpResponse {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let params = Self::parse_query(query);
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
 
-        for reservation in self.reservations.values() {
-            if reservation.user_id == user_id && !reservation.is_cancelled {
-                let flight = self.flights.get(&reservation.flight_id).unwrap();
-                let flight_dt = self.get_flight_datetime(&reservation.date, flight).unwrap();
-                valid_reservations.push((flight_dt, reservation.reservation_id, reservation));
+        match (method, segments.as_slice()) {
+            ("POST", ["reserve"]) => self.handle_reserve(body),
+            ("DELETE", ["reservation", reservation_id]) => {
+                self.handle_cancel(reservation_id, &params)
+            }
+            ("GET", ["flights"]) => self.handle_flights(&params),
+            ("GET", ["seat-search", flight_id, date @ ..]) if !date.is_empty() => {
+                self.handle_seat_search(flight_id, &date.join("/"), &params)
             }
+            (_, ["reserve"])
+            | (_, ["reservation", _])
+            | (_, ["flights"])
+            | (_, ["seat-search", ..]) => HttpResponse::error(405, "method not allowed"),
+            _ => HttpResponse::error(404, "not found"),
         }
+    }
 
-        valid_reservations.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+    fn handle_reserve(&mut self, body: &str) -> HttpResponse {
+        let request = match JsonValue::parse(body) {
+            Some(request @ JsonValue::Object(_)) => request,
+            _ => return HttpResponse::error(400, "invalid body"),
+        };
+        let field = |key: &str| request.get(key).and_then(JsonValue::as_str);
+        let (datetime, user_id, date, seat_id) =
+            match (field("datetime"), field("user_id"), field("date"), field("seat_id")) {
+                (Some(datetime), Some(user_id), Some(date), Some(seat_id)) => {
+                    (datetime, user_id, date, seat_id)
+                }
+                _ => return HttpResponse::error(400, "invalid body"),
+            };
+        let flight_id = match request.get("flight_id").and_then(JsonValue::as_u32) {
+            Some(flight_id) => flight_id,
+            None => return HttpResponse::error(400, "invalid body"),
+        };
+        if seat_id.is_empty() || !seat_id.is_ascii() {
+            return HttpResponse::error(400, "invalid seat_id");
+        }
 
-        let mut result = vec![format!("get-reservations: {}", valid_reservations.len())];
+        let result = self
+            .system
+            .process_reserve(datetime, user_id, date, flight_id, seat_id);
+        HttpResponse::from_result(&self.system, &result, 201, |message| {
+            let mut parts = message.split_whitespace();
+            let mut number = || JsonValue::Number(parts.next().unwrap().parse().unwrap());
+            JsonValue::Object(vec![
+                ("reservation_id".to_string(), number()),
+                ("price".to_string(), number()),
+            ])
+        })
+    }
 
-        for (_, _, reservation) in valid_reservations {
-            let flight = self.flights.get(&reservation.flight_id).unwrap();
-            result.push(format!(
-                "reservation id: {}, price: {}, seat: {} {} {}, route: {} ({}) -> {} ({})",
-                reservation.reservation_id,
-                reservation.price,
-                reservation.date,
-                reservation.flight_id,
-                reservation.seat_id,
-                flight.departure_airport,
-                flight.departure_time,
-                flight.arrival_airport,
-                flight.arrival_time
-            ));
+    fn handle_cancel(
+        &mut self,
+        reservation_id: &str,
+        params: &HashMap<String, String>,
+    ) -> HttpResponse {
+        let reservation_id: u32 = match reservation_id.parse() {
+            Ok(reservation_id) => reservation_id,
+            Err(_) => return HttpResponse::error(400, "invalid reservation id"),
+        };
+        let (datetime, user_id) = match (params.get("datetime"), params.get("user_id")) {
+            (Some(datetime), Some(user_id)) => (datetime, user_id),
+            _ => return HttpResponse::error(400, "missing parameter"),
+        };
+
+        let result = self.system.process_cancel(datetime, user_id, reservation_id);
+        HttpResponse::from_result(&self.system, &result, 200, |_| {
+            JsonValue::Object(vec![(
+                "reservation_id".to_string(),
+                JsonValue::Number(reservation_id as f64),
+            )])
+        })
+    }
+
+    fn handle_flights(&self, params: &HashMap<String, String>) -> HttpResponse {
+        let date = match params.get("date") {
+            Some(date) => date,
+            None => return HttpResponse::error(400, "missing parameter"),
+        };
+        let route = params
+            .get("from")
+            .zip(params.get("to"))
+            .and_then(|(from, to)| Some((from.parse::<u32>().ok()?, to.parse::<u32>().ok()?)));
+        let (departure_airport, arrival_airport) = match route {
+            Some(route) => route,
+            None => return HttpResponse::error(400, "missing parameter"),
+        };
+
+        let mut flights = vec![];
+        for flight in self
+            .system
+            .matching_flights(date, departure_airport, arrival_airport)
+        {
+            let available = self.system.available_seats_per_class(date, flight);
+            let classes = flight
+                .seat_classes
+                .iter()
+                .enumerate()
+                .map(|(i, seat_class)| {
+                    JsonValue::Object(vec![
+                        ("class".to_string(), JsonValue::Number((i + 1) as f64)),
+                        ("available".to_string(), JsonValue::Number(available[i] as f64)),
+                        ("price".to_string(), JsonValue::Number(seat_class.price as f64)),
+                    ])
+                })
+                .collect();
+            flights.push(JsonValue::Object(vec![
+                ("flight_id".to_string(), JsonValue::Number(flight.flight_id as f64)),
+                ("departure_time".to_string(), JsonValue::String(flight.departure_time.clone())),
+                ("arrival_time".to_string(), JsonValue::String(flight.arrival_time.clone())),
+                ("classes".to_string(), JsonValue::Array(classes)),
+            ]));
         }
 
-        result.join("\n")
+        HttpResponse::json(200, JsonValue::Array(flights))
     }
 
-    fn process_flight_search(
+    fn handle_seat_search(
         &self,
-        _current_datetime: &str,
+        flight_id: &str,
         date: &str,
-        departure_airport: u32,
-        arrival_airport: u32,
-    ) -> String {
-        let mut matching_flights = vec![];
-
-        for flight in self.flights.values() {
-            if flight.departure_airport == departure_airport
-                && flight.arrival_airport == arrival_airport
-            {
-                matching_flights.push((flight.departure_time.clone(), flight.flight_id, flight));
-            }
+        _params: &HashMap<String, String>,
+    ) -> HttpResponse {
+        let flight = match flight_id
+            .parse::<u32>()
+            .ok()
+            .and_then(|flight_id| self.system.flights.get(&flight_id))
+        {
+            Some(flight) if flight.operates_on(date) => flight,
+            _ => return HttpResponse::error(404, "flight not found"),
+        };
+        if self.system.parse_date(date).is_none() {
+            return HttpResponse::error(400, "invalid date");
         }
 
-        matching_flights.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+        let rows = SeatType::variants()
+            .iter()
+            .zip(self.system.seat_grid(date, flight))
+            .map(|(seat_type, row)| (seat_type.as_char().to_string(), JsonValue::String(row)))
+            .collect();
 
-        let mut result = vec![format!("flight-search: {}", matching_flights.len())];
+        HttpResponse::json(
+            200,
+            JsonValue::Object(vec![
+                ("flight_id".to_string(), JsonValue::Number(flight.flight_id as f64)),
+                ("date".to_string(), JsonValue::String(date.to_string())),
+                ("seats".to_string(), JsonValue::Object(rows)),
+            ]),
+        )
+    }
+}
 
-        for (_, _, flight) in matching_flights {
-            result.push(format!(
-                "{} {} {}",
-                flight.flight_id, flight.departure_time, flight.arrival_time
-            ));
+const USAGE: &str = "usage: flight_booking [options]
+  --input <file>        read input from file instead of stdin
+  --output <file>       write output to file instead of stdout
+  --state <file>        restore state from file on startup and save it on exit
+  --checkpoint <file>   resume from a checkpoint written by set-checkpoint
+  --json                emit one JSON object per response
+  --lang-file <file>    load a message catalog
+  --admin-token <token> override the admin token
+  --seat-events         print seat events to stderr
+  --http                read queries as HTTP requests
+  --cors-origin <origin> allow CORS requests from origin (repeatable)
+  --event-sourcing      record mutations in the event log
+  --log-notifications   print reservation and delay notifications to stderr
+  --help                print this help";
 
-            for (i, seat_class) in flight.seat_classes.iter().enumerate() {
-                let mut seats_count = 0;
-                let start_row = if i == 0 {
-                    1
-                } else {
-                    flight.seat_classes[i - 1].column + 1
-                };
-                for row in start_row..=seat_class.column {
-                    for seat_type in &SeatType::variants() {
-                        let seat_id = format!("{}{}", row, seat_type.as_char());
-                        if !self.is_seat_reserved(date, flight.flight_id, &seat_id) {
-                            seats_count += 1;
-                        }
-                    }
-                }
-                result.push(format!(
-                    "class {}: {} seats available. price = {}",
-                    i + 1,
-                    seats_count,
-                    seat_class.price
-                ));
+#[derive(Default)]
+struct Config {
+    input: Option<String>,
+    output: Option<String>,
+    state: Option<String>,
+    checkpoint: Option<String>,
+    json: bool,
+    lang_file: Option<String>,
+    admin_token: Option<String>,
+    seat_events: bool,
+    http: bool,
+    cors_origins: Vec<String>,
+    event_sourcing: bool,
+    log_notifications: bool,
+    help: bool,
+}
+
+impl Config {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut config = Config::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("error: missing value for {}", flag))
+            };
+            match flag.as_str() {
+                "--input" => config.input = Some(value()?),
+                "--output" => config.output = Some(value()?),
+                "--state" => config.state = Some(value()?),
+                "--checkpoint" => config.checkpoint = Some(value()?),
+                "--json" => config.json = true,
+                "--lang-file" => config.lang_file = Some(value()?),
+                "--admin-token" => config.admin_token = Some(value()?),
+                "--seat-events" => config.seat_events = true,
+                "--http" => config.http = true,
+                "--cors-origin" => config.cors_origins.push(value()?),
+                "--event-sourcing" => config.event_sourcing = true,
+                "--log-notifications" => config.log_notifications = true,
+                "--help" => config.help = true,
+                _ => return Err(format!("error: unknown flag {}", flag)),
             }
         }
-
-        result.join("\n")
+        if config.state.is_some() && config.checkpoint.is_some() {
+            return Err("error: --state and --checkpoint cannot be combined".to_string());
+        }
+        Ok(config)
     }
 }
 
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
 fn main() {
-    let stdin = io::stdin();
-    let mut iterator = stdin.lock().lines();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = Config::parse(&args).unwrap_or_else(|err| exit_with_error(&err));
+    if config.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let input: Box<dyn BufRead> = match &config.input {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(err) => exit_with_error(&format!("error: cannot open {}: {}", path, err)),
+        },
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+    let mut output: Box<dyn Write> = match &config.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => exit_with_error(&format!("error: cannot create {}: {}", path, err)),
+        },
+        None => Box::new(io::stdout()),
+    };
+    let mut iterator = input.lines();
 
     let n: u32 = iterator.next().unwrap().unwrap().trim().parse().unwrap();
     let mut system = ReservationSystem::new();
@@ -435,7 +10526,7 @@ fn main() {
         let mut parts: Vec<String> = vec![];
         while parts.len() < 5 {
             let line = iterator.next().unwrap().unwrap();
-            parts.extend(line.trim().split_whitespace().map(|s| s.to_string()));
+            parts.extend(line.split_whitespace().map(|s| s.to_string()));
         }
         let flight_id: u32 = parts[0].parse().unwrap();
         let departure_airport: u32 = parts[1].parse().unwrap();
@@ -446,95 +10537,536 @@ fn main() {
         let s_line = iterator.next().unwrap().unwrap();
         let s: u32 = s_line.trim().parse().unwrap();
 
-        let mut seat_classes = vec![];
+        let mut seat_classes = SeatClassBuilder::new();
 
         for _ in 0..s {
             let line = iterator.next().unwrap().unwrap();
-            let mut parts = line.trim().split_whitespace();
+            let mut parts = line.split_whitespace();
             let column: u32 = parts.next().unwrap().parse().unwrap();
             let price: u32 = parts.next().unwrap().parse().unwrap();
-            seat_classes.push(SeatClass { column, price });
+            seat_classes = match seat_classes.add_class(column, price) {
+                Ok(seat_classes) => seat_classes,
+                Err(err) => exit_with_error(&format!("error: flight {}: {}", flight_id, err)),
+            };
         }
 
-        system.add_flight(
-            flight_id,
-            departure_airport,
-            arrival_airport,
-            dep_time,
-            arr_time,
-            seat_classes,
-        );
+        let flight = FlightBuilder::new(flight_id)
+            .route(departure_airport, arrival_airport)
+            .schedule(dep_time, arr_time)
+            .with_seat_class(seat_classes)
+            .build();
+        system.add_flight(flight);
     }
 
     let m_line = iterator.next().unwrap().unwrap();
     let m: u32 = m_line.trim().parse().unwrap();
 
-    for _ in 0..m {
-        let line = iterator.next().unwrap().unwrap();
-        let query_line = line.trim();
-        let query = query_line
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>();
-
-        let command = query[0].clone();
+    if let Some(path) = &config.lang_file {
+        if let Err(err) = system
+            .load_catalog(path)
+            .and_then(|lang| system.set_language(&lang))
+        {
+            exit_with_error(&err);
+        }
+    }
+    if let Some(admin_token) = &config.admin_token {
+        system.admin_token = admin_token.clone();
+    }
+    if config.event_sourcing {
+        system.enable_event_sourcing();
+    }
+    if config.log_notifications {
+        system.register_hook(Box::new(LoggingHook));
+    }
+    if let Some(path) = &config.state {
+        if let Err(err) = system.load_state(path) {
+            exit_with_error(&err);
+        }
+    }
+    let mut skipped_lines = match &config.checkpoint {
+        Some(path) => system
+            .load_checkpoint(path)
+            .unwrap_or_else(|err| exit_with_error(&err)),
+        None => 0,
+    };
 
-        if command == "reserve:" {
-            if query.len() != 6 {
-                println!("reserve: invalid query");
-                continue;
-            }
-            let datetime = &query[1];
-            let user_id = &query[2];
-            let date = &query[3];
-            let flight_id: u32 = query[4].parse().unwrap();
-            let seat_id = &query[5];
-            println!(
-                "{}",
-                system.process_reserve(datetime, user_id, date, flight_id, seat_id)
-            );
-        } else if command == "cancel:" {
-            if query.len() != 4 {
-                println!("cancel: invalid query");
-                continue;
+    let seat_events = if config.seat_events {
+        Some(system.subscribe())
+    } else {
+        None
+    };
+    let print_seat_events = || {
+        if let Some(receiver) = &seat_events {
+            while let Some(event) = receiver.try_recv() {
+                eprintln!(
+                    "seat-event: {} {} {} {}",
+                    event.event_type, event.date, event.flight_id, event.seat_id
+                );
             }
-            let datetime = &query[1];
-            let user_id = &query[2];
-            let reservation_id: u32 = query[3].parse().unwrap();
-            println!(
-                "{}",
-                system.process_cancel(datetime, user_id, reservation_id)
-            );
-        } else if command == "seat-search:" {
-            if query.len() != 4 {
-                println!("seat-search: invalid query");
-                continue;
+        }
+    };
+
+    if config.http {
+        let mut handler = HttpHandler::new(system, CorsConfig::new(config.cors_origins.clone()));
+        for _ in 0..m {
+            let line = iterator.next().unwrap().unwrap();
+            let mut parts = line.trim().splitn(3, ' ');
+            let method = parts.next().unwrap_or_default();
+            let path = parts.next().unwrap_or_default();
+            let body = parts.next().unwrap_or_default();
+            let (origin, body) = match body.strip_prefix("Origin: ") {
+                Some(rest) => {
+                    let (origin, body) = rest.split_once(' ').unwrap_or((rest, ""));
+                    (Some(origin), body)
+                }
+                None => (None, body),
+            };
+            let response = handler.handle_request(method, path, body, origin);
+            if config.json {
+                let mut entries =
+                    vec![("status".to_string(), JsonValue::Number(response.status as f64))];
+                if !response.headers.is_empty() {
+                    let headers = response
+                        .headers
+                        .iter()
+                        .map(|(name, value)| (name.clone(), JsonValue::String(value.clone())))
+                        .collect();
+                    entries.push(("headers".to_string(), JsonValue::Object(headers)));
+                }
+                entries.push(("body".to_string(), JsonValue::String(response.body)));
+                writeln!(output, "{}", JsonValue::Object(entries)).unwrap();
+            } else {
+                writeln!(output, "{} {}", response.status, response.body).unwrap();
+                for (name, value) in &response.headers {
+                    writeln!(output, "  {}: {}", name, value).unwrap();
+                }
             }
-            let datetime = &query[1];
-            let date = &query[2];
-            let flight_id: u32 = query[3].parse().unwrap();
-            println!("{}", system.process_seat_search(datetime, date, flight_id));
-        } else if command == "get-reservations:" {
-            if query.len() != 3 {
-                println!("get-reservations: invalid query");
-                continue;
+            print_seat_events();
+        }
+        system = handler.system;
+    } else {
+        let mut query_lines = iterator.map(|line| line.unwrap());
+        let resumed = system.processed_query_count.min(m);
+        while skipped_lines > 0 && query_lines.next().is_some() {
+            skipped_lines -= 1;
+        }
+        for _ in resumed..m {
+            let line = query_lines.next().unwrap();
+            let response = system.process_query(line.trim(), &mut query_lines);
+            if config.json {
+                let entries = vec![
+                    ("query".to_string(), JsonValue::String(line.trim().to_string())),
+                    ("response".to_string(), JsonValue::String(response)),
+                ];
+                writeln!(output, "{}", JsonValue::Object(entries)).unwrap();
+            } else {
+                writeln!(output, "{}", response).unwrap();
             }
-            let datetime = &query[1];
-            let user_id = &query[2];
-            println!("{}", system.process_get_reservations(datetime, user_id));
-        } else if command == "flight-search:" {
-            if query.len() != 5 {
-                println!("flight-search: invalid query");
-                continue;
+            print_seat_events();
+            if let Err(err) = system.finish_input_query() {
+                eprintln!("{}", err);
             }
-            let datetime = &query[1];
-            let date = &query[2];
-            let departure_airport: u32 = query[3].parse().unwrap();
-            let arrival_airport: u32 = query[4].parse().unwrap();
-            println!(
-                "{}",
-                system.process_flight_search(datetime, date, departure_airport, arrival_airport)
+        }
+    }
+    output.flush().unwrap();
+
+    if let Some(path) = &config.state {
+        if let Err(err) = system.save_state(path) {
+            exit_with_error(&err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_system() -> ReservationSystem {
+        let mut system = ReservationSystem::new();
+        system.add_flight(
+            FlightBuilder::new(1)
+                .route(100, 200)
+                .schedule("09:00:00".to_string(), "11:00:00".to_string())
+                .with_seat_class(SeatClassBuilder::from(vec![(5, 30000), (10, 20000), (20, 10000)]))
+                .build(),
+        );
+        system
+    }
+
+    fn test_handler() -> HttpHandler {
+        HttpHandler::new(test_system(), CorsConfig::new(vec!["https://a.example".to_string()]))
+    }
+
+    fn reserve_body(seat_id: &str) -> String {
+        format!(
+            concat!(
+                r#"{{"datetime":"2024/01/01-10:00:00","user_id":"alice","#,
+                r#""date":"2024/02/01","flight_id":1,"seat_id":"{}"}}"#
+            ),
+            seat_id
+        )
+    }
+
+    fn body(response: &HttpResponse) -> JsonValue {
+        JsonValue::parse(&response.body).unwrap()
+    }
+
+    #[test]
+    fn http_reserve_returns_created_with_reservation() {
+        let mut handler = test_handler();
+        let response = handler.handle_request("POST", "/reserve", &reserve_body("1A"), None);
+        assert_eq!(response.status, 201);
+        assert_eq!(body(&response).get("reservation_id").and_then(JsonValue::as_u32), Some(1));
+        assert!(body(&response).get("price").and_then(JsonValue::as_u32).is_some());
+    }
+
+    #[test]
+    fn http_reserve_error_statuses() {
+        let mut handler = test_handler();
+        handler.handle_request("POST", "/reserve", &reserve_body("1A"), None);
+        let response = handler.handle_request("POST", "/reserve", &reserve_body("1A"), None);
+        assert_eq!(response.status, 409);
+        assert_eq!(
+            body(&response).get("error").and_then(JsonValue::as_str),
+            Some("already reserved")
+        );
+
+        let response = handler.handle_request("POST", "/reserve", "not json", None);
+        assert_eq!(response.status, 400);
+        let missing_flight = reserve_body("1A").replace(r#""flight_id":1"#, r#""flight_id":9"#);
+        let response = handler.handle_request("POST", "/reserve", &missing_flight, None);
+        assert_eq!(response.status, 404);
+        for seat_id in ["", "1\u{3042}"] {
+            let response = handler.handle_request("POST", "/reserve", &reserve_body(seat_id), None);
+            assert_eq!(response.status, 400);
+            assert_eq!(
+                body(&response).get("error").and_then(JsonValue::as_str),
+                Some("invalid seat_id")
+            );
+            let result = handler.system.process_reserve(
+                "2024/01/01-10:00:00",
+                "alice",
+                "2024/02/01",
+                1,
+                seat_id,
             );
+            assert_eq!(result, "reserve: invalid seat_id");
+        }
+    }
+
+    #[test]
+    fn http_cancel_statuses() {
+        let mut handler = test_handler();
+        handler.handle_request("POST", "/reserve", &reserve_body("1A"), None);
+        let path = "/reservation/1?datetime=2024/01/01-11:00:00&user_id=";
+
+        let response = handler.handle_request("DELETE", &format!("{}bob", path), "", None);
+        assert_eq!(response.status, 403);
+        let response = handler.handle_request("DELETE", &format!("{}alice", path), "", None);
+        assert_eq!(response.status, 200);
+        assert_eq!(body(&response).get("reservation_id").and_then(JsonValue::as_u32), Some(1));
+        let response = handler.handle_request("DELETE", "/reservation/1", "", None);
+        assert_eq!(response.status, 400);
+        let response = handler.handle_request(
+            "DELETE",
+            "/reservation/7?datetime=2024/01/01-11:00:00&user_id=alice",
+            "",
+            None,
+        );
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn http_flights_and_seat_search() {
+        let mut handler = test_handler();
+        let response =
+            handler.handle_request("GET", "/flights?date=2024/02/01&from=100&to=200", "", None);
+        assert_eq!(response.status, 200);
+        match body(&response) {
+            JsonValue::Array(flights) => assert_eq!(flights.len(), 1),
+            _ => panic!("expected an array"),
+        }
+        let response = handler.handle_request("GET", "/flights?date=2024/02/01", "", None);
+        assert_eq!(response.status, 400);
+
+        let response = handler.handle_request("GET", "/seat-search/1/2024/02/01", "", None);
+        assert_eq!(response.status, 200);
+        assert!(body(&response).get("seats").is_some());
+        let response = handler.handle_request("GET", "/seat-search/9/2024/02/01", "", None);
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn http_routing_and_cors() {
+        let mut handler = test_handler();
+        assert_eq!(handler.handle_request("PUT", "/reserve", "", None).status, 405);
+        assert_eq!(handler.handle_request("GET", "/nowhere", "", None).status, 404);
+
+        let response = handler.handle_request("OPTIONS", "/reserve", "", Some("https://a.example"));
+        assert_eq!(response.status, 200);
+        assert!(response
+            .headers
+            .iter()
+            .any(|(name, value)| name == "Access-Control-Allow-Origin"
+                && value == "https://a.example"));
+        let response = handler.handle_request("GET", "/flights", "", Some("https://b.example"));
+        assert_eq!(response.status, 403);
+    }
+
+    #[test]
+    fn http_status_does_not_depend_on_language() {
+        let mut handler = test_handler();
+        handler.system.load_catalog("lang/ja.txt").unwrap();
+        handler.system.set_language("ja").unwrap();
+        handler.handle_request("POST", "/reserve", &reserve_body("1A"), None);
+        let response = handler.handle_request("POST", "/reserve", &reserve_body("1A"), None);
+        assert_eq!(response.status, 409);
+        let path = "/reservation/1?datetime=2024/01/01-11:00:00&user_id=";
+        let response = handler.handle_request("DELETE", &format!("{}bob", path), "", None);
+        assert_eq!(response.status, 403);
+        let response = handler.handle_request("DELETE", &format!("{}alice", path), "", None);
+        assert_eq!(response.status, 200);
+        assert_eq!(body(&response).get("reservation_id").and_then(JsonValue::as_u32), Some(1));
+    }
+
+    #[test]
+    fn first_flyer_discount_ignores_cancelled_bookings() {
+        let mut system = test_system();
+        system.process_set_first_flyer_discount("admin", 0);
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        system.process_cancel("2024/01/01-11:00:00", "alice", 1);
+        system.process_set_first_flyer_discount("admin", 10);
+        let result = system.process_reserve("2024/01/01-12:00:00", "alice", "2024/02/01", 1, "1B");
+        assert_eq!(result, "reserve: 2 27000");
+
+        system.process_cancel("2024/01/01-13:00:00", "alice", 2);
+        let result = system.process_reserve("2024/01/01-14:00:00", "alice", "2024/02/01", 1, "1C");
+        assert_eq!(result, "reserve: 3 30000");
+    }
+
+    #[test]
+    fn remove_flight_claims_only_reservations_it_cancels() {
+        let mut system = test_system();
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        system.process_add_insurance("alice", 1, "full");
+        system.process_cancel("2024/01/01-11:00:00", "alice", 1);
+        system.process_reserve("2024/01/01-10:00:00", "bob", "2024/02/01", 1, "1B");
+        system.process_add_insurance("bob", 2, "full");
+
+        let result = system.process_remove_flight("admin", 1);
+        assert_eq!(result, "remove-flight: 1 reservations cancelled, 1 insurance claims filed");
+        assert!(system.vouchers.values().all(|voucher| voucher.reservation_id == 2));
+
+        let result = system.process_bulk_price_update("admin", 100, 200, 1, 35000);
+        assert_eq!(result, "bulk-price-update: 0 flights updated class 1 to price 35000");
+        assert_eq!(system.flights[&1].seat_classes[0].price, 30000);
+    }
+
+    #[test]
+    fn adjust_boundary_keeps_last_class_at_last_row() {
+        let mut system = test_system();
+        let result = system.process_adjust_class_boundary("admin", 1, 3, 15);
+        assert_eq!(result, "adjust-boundary: invalid boundary");
+        let result = system.process_adjust_class_boundary("admin", 1, 2, 12);
+        assert!(result.starts_with("adjust-boundary: flight 1 class 2 boundary moved to row 12"));
+        let result = system.process_seat_search("2024/01/01-10:00:00", "2024/02/01", 1);
+        assert!(result.starts_with("seat-search:"));
+    }
+
+    struct RecordingHook {
+        calls: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+    }
+
+    impl NotificationHook for RecordingHook {
+        fn on_reservation_created(&self, r: &Reservation) {
+            self.calls.borrow_mut().push(format!("created {} {}", r.reservation_id, r.seat_id));
+        }
+        fn on_reservation_cancelled(&self, r: &Reservation) {
+            self.calls.borrow_mut().push(format!("cancelled {} {}", r.reservation_id, r.seat_id));
+        }
+        fn on_flight_delayed(&self, flight_id: u32, new_departure: &str) {
+            self.calls.borrow_mut().push(format!("delayed {} {}", flight_id, new_departure));
+        }
+    }
+
+    #[test]
+    fn hooks_see_reserve_then_cancel() {
+        let mut system = test_system();
+        let calls = std::rc::Rc::default();
+        system.register_hook(Box::new(RecordingHook {
+            calls: std::rc::Rc::clone(&calls),
+        }));
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        system.process_reserve("2024/01/01-10:00:00", "bob", "2024/02/01", 1, "1A");
+        system.process_cancel("2024/01/01-11:00:00", "alice", 1);
+        assert_eq!(*calls.borrow(), ["created 1 1A", "cancelled 1 1A"]);
+    }
+
+    #[test]
+    fn generated_flights_cover_every_row() {
+        let mut system = ReservationSystem::new();
+        system.process_generate_test_data("admin", 50, 3, 10, 42);
+        for flight in system.flights.values() {
+            assert_eq!(flight.seat_classes.last().map(|class| class.column), Some(SEAT_ROWS));
+            system.process_seat_search("2024/01/01-10:00:00", "2024/02/01", flight.flight_id);
         }
     }
+
+    thread_local! {
+        static FAIL_POINT: std::cell::Cell<Option<&'static str>> =
+            const { std::cell::Cell::new(None) };
+    }
+
+    pub(super) fn fail_point(name: &str) {
+        if FAIL_POINT.with(|fail_point| fail_point.get()) == Some(name) {
+            panic!("injected failure at {}", name);
+        }
+    }
+
+    #[test]
+    fn reserve_rolls_back_failure_between_mutations() {
+        let mut system = test_system();
+        system.enable_event_sourcing();
+        let promo = PromoCode {
+            discount: PromoDiscount::Percent(10),
+            max_uses: 5,
+            uses: 0,
+            expiry_date: NaiveDate::from_ymd_opt(2030, 1, 1).unwrap(),
+        };
+        system.promo_codes.insert("SAVE10".to_string(), promo);
+        let options = ReserveOptions {
+            promo_code: Some("SAVE10".to_string()),
+            ..ReserveOptions::default()
+        };
+        let events_before = system.event_store.as_ref().unwrap().events.len();
+
+        FAIL_POINT.with(|fail_point| fail_point.set(Some("insert_reservation")));
+        let result = system.process_reserve_with_options(
+            "2024/01/01-10:00:00",
+            "alice",
+            "2024/02/01",
+            1,
+            "1A",
+            &options,
+        );
+        FAIL_POINT.with(|fail_point| fail_point.set(None));
+        assert_eq!(result, "reserve: internal error");
+        assert!(system.reservations.is_empty());
+        assert!(!system.is_seat_reserved("2024/02/01", 1, "1A"));
+        assert_eq!(system.next_reservation_id, 1);
+        assert_eq!(system.promo_codes["SAVE10"].uses, 0);
+        assert!(system.price_history.is_empty());
+        assert_eq!(system.event_store.as_ref().unwrap().events.len(), events_before);
+
+        let result = system.process_reserve_with_options(
+            "2024/01/01-10:00:00",
+            "alice",
+            "2024/02/01",
+            1,
+            "1A",
+            &options,
+        );
+        assert_eq!(result, "reserve: 1 27000");
+        assert_eq!(system.promo_codes["SAVE10"].uses, 1);
+    }
+
+    fn drain(receiver: &SeatEventReceiver) -> Vec<String> {
+        std::iter::from_fn(|| receiver.try_recv())
+            .map(|event| {
+                format!(
+                    "{} {} {} {}",
+                    event.event_type, event.date, event.flight_id, event.seat_id
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seat_events_for_reserve_then_cancel() {
+        let mut system = test_system();
+        let receiver = system.subscribe();
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        system.process_cancel("2024/01/01-11:00:00", "alice", 1);
+        assert_eq!(drain(&receiver), ["reserved 2024/02/01 1 1A", "freed 2024/02/01 1 1A"]);
+    }
+
+    #[test]
+    fn seat_events_survive_dropped_receiver() {
+        let mut system = test_system();
+        drop(system.subscribe());
+        let receiver = system.subscribe();
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        assert_eq!(drain(&receiver), ["reserved 2024/02/01 1 1A"]);
+    }
+
+    #[test]
+    fn event_store_records_reserve_then_cancel() {
+        let mut system = test_system();
+        system.enable_event_sourcing();
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        system.process_cancel("2024/01/01-11:00:00", "alice", 1);
+        let types: Vec<String> = system
+            .event_store
+            .as_ref()
+            .unwrap()
+            .events
+            .iter()
+            .filter_map(|event| event.to_json().get("type")?.as_str().map(str::to_string))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "flight_added",
+                "reservation_created",
+                "seat_reserved",
+                "reservation_cancelled",
+                "seat_freed"
+            ]
+        );
+    }
+
+    #[test]
+    fn flight_search_cache_drops_stale_prices() {
+        let mut system = test_system();
+        system.process_set_cache_ttl("admin", 3600);
+        let search = |system: &mut ReservationSystem| {
+            system.process_flight_search("2024/01/01-10:00:00", "2024/02/01", 100, 200, None, None)
+        };
+        assert!(search(&mut system).contains("price = 30000"));
+
+        system.process_bulk_price_update("admin", 100, 200, 1, 35000);
+        let result = search(&mut system);
+        assert!(result.contains("price = 35000"), "{}", result);
+        system.process_update_fuel_surcharge("admin", 1, 5000);
+        assert!(search(&mut system).contains("surcharge"));
+    }
+
+    #[test]
+    fn respond_swap_rechecks_peak_dates() {
+        let mut system = test_system();
+        system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        system.process_reserve("2024/01/01-10:00:00", "bob", "2024/02/01", 1, "15A");
+        let result =
+            system.process_create_swap_request("2024/01/01-11:00:00", "alice", 1, "bob", 2);
+        assert_eq!(result, "create-swap: request 1 sent to bob");
+        system.process_set_peak_dates("admin", "2024/02/01");
+
+        let result = system.process_respond_swap("2024/01/01-12:00:00", "bob", 1, true);
+        assert_eq!(result, "respond-swap: not allowed on peak date");
+        assert_eq!(system.reservations[&1].seat_id, "1A");
+        assert_eq!(system.reservations[&2].seat_id, "15A");
+    }
+
+    #[test]
+    fn service_fee_is_bounded() {
+        let mut system = test_system();
+        let result = system.process_set_service_fee("admin", 4294967000);
+        assert_eq!(result, "set-service-fee: invalid fee");
+        let result = system.process_reserve("2024/01/01-10:00:00", "alice", "2024/02/01", 1, "1A");
+        assert_eq!(result, "reserve: 1 27000");
+
+        system.process_set_service_fee("admin", MAX_SERVICE_FEE);
+        let result = system.process_reserve("2024/01/01-10:00:00", "bob", "2024/02/01", 1, "1B");
+        assert_eq!(result, "reserve: 2 127000 (base=27000 fee=100000)");
+    }
 }